@@ -0,0 +1,123 @@
+use {
+    crate::{markdown, model::Model, relation::Relation, Error},
+    axum::{
+        extract::Path,
+        http::{header, StatusCode},
+        response::{IntoResponse, Response},
+        routing::get,
+        Extension, Json, Router,
+    },
+    serde_json::{json, Value},
+    std::{
+        collections::BTreeMap,
+        sync::{Arc, RwLock},
+        time::SystemTime,
+    },
+};
+
+#[derive(Default)]
+struct Status {
+    model: Arc<Model>,
+    diagrams: BTreeMap<String, String>,
+    built: Option<SystemTime>,
+    succeeded: Option<SystemTime>,
+    error: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Api {
+    status: Arc<RwLock<Status>>,
+}
+
+impl Api {
+    pub(crate) fn succeeded(&self, model: &Arc<Model>, diagrams: &BTreeMap<String, String>) {
+        let now = SystemTime::now();
+        *self.status.write().unwrap() = Status {
+            model: Arc::clone(model),
+            diagrams: diagrams.clone(),
+            built: Some(now),
+            succeeded: Some(now),
+            error: None,
+        };
+    }
+
+    pub(crate) fn failed(&self, error: &Error) {
+        let mut status = self.status.write().unwrap();
+        status.built = Some(SystemTime::now());
+        status.error = Some(error.to_string());
+    }
+
+    fn model(&self) -> Arc<Model> {
+        Arc::clone(&self.status.read().unwrap().model)
+    }
+}
+
+fn relations(model: &Model) -> impl Iterator<Item = Relation> + '_ {
+    model.nodes.iter().flat_map(|(left, node)| {
+        node.relations
+            .iter()
+            .flatten()
+            .flat_map(move |(right, relations)| {
+                relations.iter().map(move |relation| Relation {
+                    left: Some(left.clone()),
+                    right: Some(right.clone()),
+                    ..relation.clone()
+                })
+            })
+    })
+}
+
+async fn nodes(Extension(api): Extension<Api>) -> Json<Value> {
+    Json(json!(api.model().nodes))
+}
+
+async fn node(Path(id): Path<String>, Extension(api): Extension<Api>) -> Response {
+    let model = api.model();
+    let node = match model.nodes.get(&id) {
+        Some(node) => node,
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let (outgoing, incoming): (Vec<_>, Vec<_>) = relations(&model)
+        .filter(|relation| {
+            relation.left.as_ref() == Some(&id) || relation.right.as_ref() == Some(&id)
+        })
+        .partition(|relation| relation.left.as_ref() == Some(&id));
+    let mut value = json!(node);
+    value["outgoing"] = json!(outgoing);
+    value["incoming"] = json!(incoming);
+    Json(value).into_response()
+}
+
+async fn all_relations(Extension(api): Extension<Api>) -> Json<Value> {
+    Json(json!(relations(&api.model()).collect::<Vec<_>>()))
+}
+
+async fn diagram(Path(name): Path<String>, Extension(api): Extension<Api>) -> Response {
+    match api.status.read().unwrap().diagrams.get(&name) {
+        Some(diagram) => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            format!("{}\n", markdown::strip_fence(diagram)),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn status(Extension(api): Extension<Api>) -> Json<Value> {
+    let status = api.status.read().unwrap();
+    Json(json!({
+        "built": status.built.map(httpdate::fmt_http_date),
+        "succeeded": status.succeeded.map(httpdate::fmt_http_date),
+        "error": status.error,
+    }))
+}
+
+pub(crate) fn router(api: Api) -> Router {
+    Router::new()
+        .route("/api/nodes", get(nodes))
+        .route("/api/nodes/:id", get(node))
+        .route("/api/relations", get(all_relations))
+        .route("/api/diagrams/:name", get(diagram))
+        .route("/api/status", get(status))
+        .layer(Extension(api))
+}
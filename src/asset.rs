@@ -0,0 +1,42 @@
+use {
+    crate::Result,
+    globset::{Glob, GlobSet, GlobSetBuilder},
+    std::path::Path,
+};
+
+pub(crate) struct Filter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    patterns
+        .iter()
+        .try_fold(GlobSetBuilder::new(), |mut builder, pattern| -> Result<_> {
+            builder.add(Glob::new(pattern)?);
+            Ok(builder)
+        })?
+        .build()
+        .map_err(|err| err.into())
+}
+
+impl Filter {
+    pub(crate) fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(glob_set(include)?)
+            },
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        self.include
+            .as_ref()
+            .map(|include| include.is_match(path))
+            .unwrap_or(true)
+            && !self.exclude.is_match(path)
+    }
+}
@@ -0,0 +1,133 @@
+use {
+    crate::{Error, Options, Result, Strictness},
+    serde::{Deserialize, Deserializer},
+    std::{
+        collections::BTreeMap,
+        fs::File,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        time::Duration,
+    },
+};
+
+pub const CONFIG: &str = "symo.yaml";
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Paths {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+fn paths<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<PathBuf>, D::Error> {
+    Ok(match Paths::deserialize(deserializer)? {
+        Paths::One(path) => vec![path],
+        Paths::Many(paths) => paths,
+    })
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, deserialize_with = "paths")]
+    pub model: Vec<PathBuf>,
+    #[serde(default, deserialize_with = "paths")]
+    pub template: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub addr: Option<SocketAddr>,
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    pub debounce: Option<u64>,
+    pub poll: Option<u64>,
+    pub strictness: Option<Strictness>,
+    #[serde(default)]
+    pub packages: BTreeMap<String, PathBuf>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    pub banner: Option<bool>,
+    pub export: Option<PathBuf>,
+    pub svg: Option<PathBuf>,
+    pub svg_links: Option<bool>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config: Self =
+            serde_yaml::from_reader(File::open(path)?).map_err(|source| Error::Config {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let resolve = |paths: Vec<PathBuf>| paths.into_iter().map(|path| dir.join(path)).collect();
+        Ok(Self {
+            model: resolve(config.model),
+            template: resolve(config.template),
+            output: config.output.map(|path| dir.join(path)),
+            packages: config
+                .packages
+                .into_iter()
+                .map(|(name, path)| (name, dir.join(path)))
+                .collect(),
+            ..config
+        })
+    }
+
+    pub fn discover(dir: &Path) -> Result<Option<Self>> {
+        dir.ancestors()
+            .map(|dir| dir.join(CONFIG))
+            .find(|path| path.is_file())
+            .map(|path| Self::load(&path))
+            .transpose()
+    }
+
+    pub fn or(self, other: Self) -> Self {
+        fn or<T>(list: Vec<T>, other: Vec<T>) -> Vec<T> {
+            if list.is_empty() {
+                other
+            } else {
+                list
+            }
+        }
+        Self {
+            model: or(self.model, other.model),
+            template: or(self.template, other.template),
+            output: self.output.or(other.output),
+            addr: self.addr.or(other.addr),
+            include: or(self.include, other.include),
+            exclude: or(self.exclude, other.exclude),
+            debounce: self.debounce.or(other.debounce),
+            poll: self.poll.or(other.poll),
+            strictness: self.strictness.or(other.strictness),
+            packages: other.packages.into_iter().chain(self.packages).collect(),
+            extensions: or(self.extensions, other.extensions),
+            banner: self.banner.or(other.banner),
+            export: self.export.or(other.export),
+            svg: self.svg.or(other.svg),
+            svg_links: self.svg_links.or(other.svg_links),
+        }
+    }
+
+    pub fn options(&self) -> Options {
+        Options {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            debounce: self.debounce.map(Duration::from_millis),
+            poll: self.poll.map(Duration::from_millis),
+            strictness: self.strictness.unwrap_or_default(),
+            packages: self.packages.clone(),
+            extensions: if self.extensions.is_empty() {
+                Options::default().extensions
+            } else {
+                self.extensions.clone()
+            },
+            banner: self.banner.unwrap_or(true),
+            export: self.export.clone(),
+            svg: self.svg.clone(),
+            svg_links: self.svg_links.unwrap_or(false),
+        }
+    }
+}
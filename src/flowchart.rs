@@ -0,0 +1,1179 @@
+use std::{collections::BTreeMap, iter};
+
+const CHAR_WIDTH: f64 = 8.0;
+const LINE_HEIGHT: f64 = 18.0;
+const PADDING: f64 = 12.0;
+const NODE_GAP: f64 = 30.0;
+const RANK_GAP: f64 = 60.0;
+const CLUSTER_PADDING: f64 = 16.0;
+const CYLINDER: f64 = 8.0;
+const MARGIN: f64 = 10.0;
+const SWEEPS: usize = 4;
+const SEPARATIONS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    LeftRight,
+    RightLeft,
+    TopBottom,
+    BottomTop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Rect,
+    Round,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    Asymmetric,
+    Rhombus,
+    Hexagon,
+}
+
+const SHAPES: [(&str, &str, Shape); 10] = [
+    ("(((", ")))", Shape::Circle),
+    ("((", "))", Shape::Circle),
+    ("([", "])", Shape::Stadium),
+    ("[[", "]]", Shape::Subroutine),
+    ("[(", ")]", Shape::Cylinder),
+    ("{{", "}}", Shape::Hexagon),
+    ("[", "]", Shape::Rect),
+    ("(", ")", Shape::Round),
+    (">", "]", Shape::Asymmetric),
+    ("{", "}", Shape::Rhombus),
+];
+
+const ENTITIES: [(&str, &str); 12] = [
+    ("<br/>", "\n"),
+    ("<br />", "\n"),
+    ("<br>", "\n"),
+    ("\\n", "\n"),
+    ("&lt;", "<"),
+    ("&gt;", ">"),
+    ("&quot;", "\""),
+    ("&#x27;", "'"),
+    ("&#39;", "'"),
+    ("&#x60;", "`"),
+    ("&#x3D;", "="),
+    ("&amp;", "&"),
+];
+
+const IGNORED: [&str; 6] = [
+    "direction",
+    "classDef",
+    "class",
+    "style",
+    "linkStyle",
+    "click",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stroke {
+    Normal,
+    Dotted,
+    Thick,
+}
+
+#[derive(Debug)]
+struct Node {
+    label: Vec<String>,
+    shape: Shape,
+    cluster: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Edge {
+    from: usize,
+    to: usize,
+    label: Vec<String>,
+    stroke: Stroke,
+    arrow: bool,
+}
+
+#[derive(Debug)]
+struct Cluster {
+    title: Vec<String>,
+    parent: Option<usize>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Flowchart {
+    direction: Direction,
+    ids: BTreeMap<String, usize>,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    clusters: Vec<Cluster>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Rect {
+    fn left(&self) -> f64 {
+        self.x - self.width / 2.0
+    }
+
+    fn top(&self) -> f64 {
+        self.y - self.height / 2.0
+    }
+
+    fn right(&self) -> f64 {
+        self.x + self.width / 2.0
+    }
+
+    fn bottom(&self) -> f64 {
+        self.y + self.height / 2.0
+    }
+
+    fn union(rects: impl Iterator<Item = Rect>) -> Option<Rect> {
+        rects
+            .map(|rect| (rect.left(), rect.top(), rect.right(), rect.bottom()))
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+            .map(|(left, top, right, bottom)| Rect {
+                x: (left + right) / 2.0,
+                y: (top + bottom) / 2.0,
+                width: right - left,
+                height: bottom - top,
+            })
+    }
+}
+
+struct Graph {
+    rank: Vec<usize>,
+    paths: Vec<Vec<usize>>,
+    routes: Vec<Option<Vec<usize>>>,
+}
+
+fn label_segment(route: &[usize]) -> usize {
+    (route.len() - 2) / 2
+}
+
+fn lines(text: &str) -> Vec<String> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text);
+    ENTITIES
+        .iter()
+        .fold(text.to_string(), |text, (entity, c)| {
+            text.replace(entity, c)
+        })
+        .split('\n')
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+fn split_id(text: &str) -> (&str, &str) {
+    let end = text
+        .char_indices()
+        .find(|(i, c)| {
+            !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                || ["--", "-."]
+                    .iter()
+                    .any(|arrow| text[*i..].starts_with(arrow))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+fn split_shape(text: &str) -> Option<(Vec<String>, Shape, &str)> {
+    SHAPES.iter().find_map(|(open, close, shape)| {
+        let rest = text.strip_prefix(open)?;
+        let start = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.find('"')? + 1,
+            None => 0,
+        };
+        let end = start + rest[start..].find(close)?;
+        if *shape == Shape::Rect && rest.starts_with(['/', '\\']) {
+            return None;
+        }
+        Some((lines(&rest[..end]), *shape, &rest[end + close.len()..]))
+    })
+}
+
+fn split_arrow(text: &str) -> Option<(Stroke, bool, Vec<String>, &str)> {
+    let text = text.trim_start();
+    let end = text
+        .find(|c: char| !matches!(c, '-' | '.' | '=' | '>'))
+        .unwrap_or(text.len());
+    let arrow = &text[..end];
+    let line = arrow.strip_suffix('>').unwrap_or(arrow);
+    let length = if line.len() < arrow.len() { 2 } else { 3 };
+    let stroke = match line
+        .strip_prefix('-')
+        .and_then(|line| line.strip_suffix('-'))
+    {
+        Some(dots) if !dots.is_empty() && dots.chars().all(|c| c == '.') => Stroke::Dotted,
+        _ if line.len() >= length && line.chars().all(|c| c == '-') => Stroke::Normal,
+        _ if line.len() >= length && line.chars().all(|c| c == '=') => Stroke::Thick,
+        _ => return None,
+    };
+    let rest = text[end..].trim_start();
+    let (label, rest) = match rest.strip_prefix('|') {
+        Some(rest) => {
+            let start = match rest.strip_prefix('"') {
+                Some(quoted) => quoted.find('"')? + 1,
+                None => 0,
+            };
+            let end = start + rest[start..].find('|')?;
+            (lines(&rest[..end]), &rest[end + 1..])
+        }
+        None => (vec![], rest),
+    };
+    Some((stroke, arrow.ends_with('>'), label, rest))
+}
+
+fn text_size(lines: &[String]) -> (f64, f64) {
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as f64
+        * CHAR_WIDTH;
+    let height = if lines.iter().all(|line| line.is_empty()) {
+        0.0
+    } else {
+        lines.len() as f64 * LINE_HEIGHT
+    };
+    (width, height)
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn text(lines: &[String], x: f64, y: f64) -> String {
+    let first = y - (lines.len() as f64 - 1.0) * LINE_HEIGHT / 2.0 + 5.0;
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            format!(
+                "<text x=\"{x:.1}\" y=\"{:.1}\">{}</text>\n",
+                first + i as f64 * LINE_HEIGHT,
+                escape(line)
+            )
+        })
+        .collect()
+}
+
+fn points(points: &[(f64, f64)]) -> String {
+    format!(
+        "<polygon points=\"{}\"/>\n",
+        points
+            .iter()
+            .map(|(x, y)| format!("{x:.1},{y:.1}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    )
+}
+
+impl Node {
+    fn size(&self) -> (f64, f64) {
+        let (text_width, text_height) = text_size(&self.label);
+        let (width, height) = (text_width + 2.0 * PADDING, text_height + 2.0 * PADDING);
+        match self.shape {
+            Shape::Rect | Shape::Round => (width, height),
+            Shape::Circle => {
+                let diameter = text_width.hypot(text_height) + PADDING;
+                (diameter, diameter)
+            }
+            Shape::Rhombus => (width * 1.5, height * 1.5),
+            Shape::Stadium => (width + height, height),
+            Shape::Hexagon | Shape::Asymmetric => (width + height / 2.0, height),
+            Shape::Subroutine => (width + 2.0 * PADDING, height),
+            Shape::Cylinder => (width, height + 2.0 * CYLINDER),
+        }
+    }
+
+    fn clip(&self, rect: &Rect, x: f64, y: f64) -> (f64, f64) {
+        let (dx, dy) = (x - rect.x, y - rect.y);
+        if dx == 0.0 && dy == 0.0 {
+            return (rect.x, rect.y);
+        }
+        let (half_width, half_height) = (rect.width / 2.0, rect.height / 2.0);
+        let scale = match self.shape {
+            Shape::Circle => half_width / dx.hypot(dy),
+            Shape::Rhombus => 1.0 / (dx.abs() / half_width + dy.abs() / half_height),
+            _ => (half_width / dx.abs()).min(half_height / dy.abs()),
+        };
+        (rect.x + dx * scale, rect.y + dy * scale)
+    }
+
+    fn draw(&self, rect: &Rect) -> String {
+        let (left, top, right, bottom) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+        let (width, height) = (rect.width, rect.height);
+        let box_with = |radius: f64| {
+            format!(
+                "<rect x=\"{left:.1}\" y=\"{top:.1}\" width=\"{width:.1}\" height=\"{height:.1}\" rx=\"{radius:.1}\"/>\n"
+            )
+        };
+        match self.shape {
+            Shape::Rect => box_with(0.0),
+            Shape::Round => box_with(5.0),
+            Shape::Stadium => box_with(height / 2.0),
+            Shape::Subroutine => format!(
+                "{}<path d=\"M {:.1} {top:.1} V {bottom:.1} M {:.1} {top:.1} V {bottom:.1}\"/>\n",
+                box_with(0.0),
+                left + PADDING,
+                right - PADDING
+            ),
+            Shape::Cylinder => format!(
+                "<path d=\"M {left:.1} {:.1} a {:.1} {CYLINDER:.1} 0 0 0 {width:.1} 0 a {:.1} {CYLINDER:.1} 0 0 0 {:.1} 0 l 0 {:.1} a {:.1} {CYLINDER:.1} 0 0 0 {width:.1} 0 l 0 {:.1}\"/>\n",
+                top + CYLINDER,
+                width / 2.0,
+                width / 2.0,
+                -width,
+                height - 2.0 * CYLINDER,
+                width / 2.0,
+                2.0 * CYLINDER - height
+            ),
+            Shape::Circle => format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\"/>\n",
+                rect.x,
+                rect.y,
+                width / 2.0
+            ),
+            Shape::Asymmetric => points(&[
+                (left, top),
+                (right, top),
+                (right, bottom),
+                (left, bottom),
+                (left + height / 4.0, rect.y),
+            ]),
+            Shape::Rhombus => points(&[
+                (rect.x, top),
+                (right, rect.y),
+                (rect.x, bottom),
+                (left, rect.y),
+            ]),
+            Shape::Hexagon => points(&[
+                (left + height / 4.0, top),
+                (right - height / 4.0, top),
+                (right, rect.y),
+                (right - height / 4.0, bottom),
+                (left + height / 4.0, bottom),
+                (left, rect.y),
+            ]),
+        }
+    }
+}
+
+impl Flowchart {
+    pub(crate) fn parse(text: &str) -> Option<Self> {
+        let mut statements = text
+            .lines()
+            .map(|line| line.trim().trim_end_matches(';').trim())
+            .filter(|line| !line.is_empty() && !line.starts_with("%%"));
+        let mut header = statements.next()?.split_whitespace();
+        if !matches!(header.next(), Some("flowchart" | "graph")) {
+            return None;
+        }
+        let direction = match header.next() {
+            Some("LR") => Direction::LeftRight,
+            Some("RL") => Direction::RightLeft,
+            Some("BT") => Direction::BottomTop,
+            Some("TB" | "TD") | None => Direction::TopBottom,
+            Some(_) => return None,
+        };
+        if header.next().is_some() {
+            return None;
+        }
+        let mut chart = Self {
+            direction,
+            ids: BTreeMap::new(),
+            nodes: vec![],
+            edges: vec![],
+            clusters: vec![],
+        };
+        let mut stack: Vec<usize> = vec![];
+        statements.try_for_each(|line| {
+            let keyword = line.split_whitespace().next().unwrap_or_default();
+            match keyword {
+                "subgraph" => {
+                    let rest = line[keyword.len()..].trim();
+                    let (id, after) = split_id(rest);
+                    let title = match (id.is_empty(), split_shape(after.trim_start())) {
+                        (false, Some((title, _, after))) => {
+                            after.trim().is_empty().then_some(title)?
+                        }
+                        _ => lines(rest),
+                    };
+                    chart.clusters.push(Cluster {
+                        title,
+                        parent: stack.last().copied(),
+                    });
+                    stack.push(chart.clusters.len() - 1);
+                    Some(())
+                }
+                "end" if line == keyword => stack.pop().map(|_| ()),
+                _ if IGNORED.contains(&keyword) => Some(()),
+                _ => chart.statement(line, stack.last().copied()),
+            }
+        })?;
+        stack.is_empty().then_some(chart)
+    }
+
+    fn node<'a>(&mut self, text: &'a str, cluster: Option<usize>) -> Option<(usize, &'a str)> {
+        let (id, rest) = split_id(text.trim_start());
+        if id.is_empty() {
+            return None;
+        }
+        let (shape, rest) = match split_shape(rest) {
+            Some((label, shape, rest)) => (Some((label, shape)), rest),
+            None => (None, rest),
+        };
+        let index = *self.ids.entry(id.to_string()).or_insert_with(|| {
+            self.nodes.push(Node {
+                label: vec![id.to_string()],
+                shape: Shape::Rect,
+                cluster: None,
+            });
+            self.nodes.len() - 1
+        });
+        let node = &mut self.nodes[index];
+        if let Some((label, shape)) = shape {
+            node.label = label;
+            node.shape = shape;
+        }
+        if cluster.is_some() {
+            node.cluster = cluster;
+        }
+        Some((index, rest))
+    }
+
+    fn statement(&mut self, line: &str, cluster: Option<usize>) -> Option<()> {
+        let (mut from, mut rest) = self.node(line, cluster)?;
+        while !rest.trim().is_empty() {
+            let (stroke, arrow, label, after) = split_arrow(rest)?;
+            let (to, after) = self.node(after, cluster)?;
+            if from == to {
+                return None;
+            }
+            self.edges.push(Edge {
+                from,
+                to,
+                label,
+                stroke,
+                arrow,
+            });
+            from = to;
+            rest = after;
+        }
+        Some(())
+    }
+
+    fn path(&self, node: usize) -> Vec<usize> {
+        let mut path: Vec<_> = iter::successors(self.nodes[node].cluster, |cluster| {
+            self.clusters[*cluster].parent
+        })
+        .collect();
+        path.reverse();
+        path
+    }
+
+    fn ranks(&self) -> Vec<usize> {
+        fn visit(
+            node: usize,
+            outgoing: &[Vec<usize>],
+            state: &mut [u8],
+            dag: &mut Vec<(usize, usize)>,
+            finished: &mut Vec<usize>,
+        ) {
+            state[node] = 1;
+            outgoing[node].iter().for_each(|&next| match state[next] {
+                0 => {
+                    dag.push((node, next));
+                    visit(next, outgoing, state, dag, finished);
+                }
+                1 => dag.push((next, node)),
+                _ => dag.push((node, next)),
+            });
+            state[node] = 2;
+            finished.push(node);
+        }
+
+        let count = self.nodes.len();
+        let mut outgoing = vec![vec![]; count];
+        self.edges
+            .iter()
+            .filter(|edge| edge.from != edge.to)
+            .for_each(|edge| outgoing[edge.from].push(edge.to));
+        let mut state = vec![0; count];
+        let mut dag = vec![];
+        let mut finished = vec![];
+        (0..count).for_each(|node| {
+            if state[node] == 0 {
+                visit(node, &outgoing, &mut state, &mut dag, &mut finished);
+            }
+        });
+        let mut rank = vec![0; count];
+        finished.iter().rev().for_each(|&node| {
+            dag.iter()
+                .filter(|(from, _)| *from == node)
+                .for_each(|&(_, to)| rank[to] = rank[to].max(rank[node] + 1));
+        });
+        finished.iter().for_each(|&node| {
+            let incoming = dag.iter().any(|(_, to)| *to == node);
+            let next = dag
+                .iter()
+                .filter(|(from, _)| *from == node)
+                .map(|(_, to)| rank[*to])
+                .min();
+            if let (false, Some(next)) = (incoming, next) {
+                rank[node] = next - 1;
+            }
+        });
+        rank
+    }
+
+    fn graph(&self) -> Graph {
+        let mut rank = self.ranks();
+        let mut paths: Vec<_> = (0..self.nodes.len()).map(|node| self.path(node)).collect();
+        let routes = self
+            .edges
+            .iter()
+            .map(|edge| {
+                if edge.from == edge.to {
+                    return None;
+                }
+                let (low, high) = if rank[edge.from] < rank[edge.to] {
+                    (edge.from, edge.to)
+                } else {
+                    (edge.to, edge.from)
+                };
+                let common: Vec<_> = paths[low]
+                    .iter()
+                    .zip(&paths[high])
+                    .take_while(|(a, b)| a == b)
+                    .map(|(cluster, _)| *cluster)
+                    .collect();
+                let mut route = vec![low];
+                (rank[low] + 1..rank[high]).for_each(|layer| {
+                    rank.push(layer);
+                    paths.push(common.clone());
+                    route.push(rank.len() - 1);
+                });
+                route.push(high);
+                if low != edge.from {
+                    route.reverse();
+                }
+                Some(route)
+            })
+            .collect();
+        Graph {
+            rank,
+            paths,
+            routes,
+        }
+    }
+
+    fn order(&self, graph: &Graph) -> Vec<Vec<usize>> {
+        let (rank, paths) = (&graph.rank, &graph.paths);
+        let segments: Vec<_> = graph
+            .routes
+            .iter()
+            .flatten()
+            .flat_map(|route| route.windows(2).map(|pair| (pair[0], pair[1])))
+            .collect();
+        let mut layers = vec![vec![]; rank.iter().max().map(|max| max + 1).unwrap_or(0)];
+        (0..rank.len()).for_each(|item| layers[rank[item]].push(item));
+        let mut position = vec![0.0; rank.len()];
+        (0..SWEEPS).for_each(|sweep| {
+            layers.iter().for_each(|layer| {
+                layer
+                    .iter()
+                    .enumerate()
+                    .for_each(|(i, item)| position[*item] = i as f64)
+            });
+            let down = sweep % 2 == 0;
+            let order: Vec<_> = if down {
+                (1..layers.len()).collect()
+            } else {
+                (0..layers.len().saturating_sub(1)).rev().collect()
+            };
+            order.into_iter().for_each(|layer| {
+                let adjacent = if down { layer - 1 } else { layer + 1 };
+                let barycenter: BTreeMap<_, _> = layers[layer]
+                    .iter()
+                    .map(|&item| {
+                        let neighbors: Vec<_> = segments
+                            .iter()
+                            .filter_map(|&(from, to)| match (from == item, to == item) {
+                                (true, _) => Some(to),
+                                (_, true) => Some(from),
+                                _ => None,
+                            })
+                            .filter(|neighbor| rank[*neighbor] == adjacent)
+                            .map(|neighbor| position[neighbor])
+                            .collect();
+                        let value = if neighbors.is_empty() {
+                            position[item]
+                        } else {
+                            neighbors.iter().sum::<f64>() / neighbors.len() as f64
+                        };
+                        (item, value)
+                    })
+                    .collect();
+                let mut clusters: BTreeMap<usize, (f64, f64)> = BTreeMap::new();
+                barycenter.iter().for_each(|(item, value)| {
+                    paths[*item].iter().for_each(|cluster| {
+                        let entry = clusters.entry(*cluster).or_default();
+                        entry.0 += value;
+                        entry.1 += 1.0;
+                    })
+                });
+                let key = |item: usize, level: usize| match paths[item].get(level) {
+                    Some(cluster) => (clusters[cluster].0 / clusters[cluster].1, 1, *cluster),
+                    None => (barycenter[&item], 0, item),
+                };
+                layers[layer].sort_by(|&a, &b| {
+                    let level = paths[a]
+                        .iter()
+                        .zip(&paths[b])
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    let (a, b) = (key(a, level), key(b, level));
+                    a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2))
+                });
+                layers[layer]
+                    .iter()
+                    .enumerate()
+                    .for_each(|(i, item)| position[*item] = i as f64);
+            });
+        });
+        layers
+    }
+
+    fn layout(&self, graph: &Graph) -> Vec<Rect> {
+        let layers = self.order(graph);
+        let horizontal = matches!(self.direction, Direction::LeftRight | Direction::RightLeft);
+        let sizes: Vec<_> = (0..graph.rank.len())
+            .map(|item| self.nodes.get(item).map(Node::size).unwrap_or_default())
+            .collect();
+        let main = |size: (f64, f64)| if horizontal { size.0 } else { size.1 };
+        let cross = |size: (f64, f64)| if horizontal { size.1 } else { size.0 };
+        let mut gaps = vec![RANK_GAP; layers.len().saturating_sub(1)];
+        self.edges
+            .iter()
+            .zip(&graph.routes)
+            .filter_map(|(edge, route)| route.as_ref().map(|route| (edge, route)))
+            .for_each(|(edge, route)| {
+                let segment = label_segment(route);
+                let gap = graph.rank[route[segment]].min(graph.rank[route[segment + 1]]);
+                gaps[gap] = gaps[gap].max(RANK_GAP + main(text_size(&edge.label)));
+            });
+        let mut mains = vec![];
+        layers
+            .iter()
+            .enumerate()
+            .fold(0.0, |start, (layer, items)| {
+                let size = items
+                    .iter()
+                    .map(|item| main(sizes[*item]))
+                    .fold(0.0, f64::max);
+                mains.push(start + size / 2.0);
+                start + size + gaps.get(layer).copied().unwrap_or_default()
+            });
+        let title = |entered: &[usize]| -> f64 {
+            entered
+                .iter()
+                .map(|cluster| {
+                    CLUSTER_PADDING
+                        + if horizontal {
+                            text_size(&self.clusters[*cluster].title).1
+                        } else {
+                            0.0
+                        }
+                })
+                .sum()
+        };
+        let mut starts = vec![0.0; graph.rank.len()];
+        layers.iter().for_each(|items| {
+            let mut previous: &[usize] = &[];
+            let mut layer_starts = vec![];
+            let end = items.iter().enumerate().fold(0.0, |cursor, (i, item)| {
+                let path = &graph.paths[*item];
+                let common = previous
+                    .iter()
+                    .zip(path)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                let gap = if i == 0 { 0.0 } else { NODE_GAP }
+                    + (previous.len() - common) as f64 * CLUSTER_PADDING
+                    + title(&path[common..]);
+                let start = cursor + gap;
+                layer_starts.push(start);
+                previous = path;
+                start + cross(sizes[*item])
+            }) + previous.len() as f64 * CLUSTER_PADDING;
+            items
+                .iter()
+                .zip(layer_starts)
+                .for_each(|(item, start)| starts[*item] = start - end / 2.0);
+        });
+        let crosses: Vec<_> = sizes.iter().map(|size| cross(*size)).collect();
+        for _ in 0..SEPARATIONS {
+            match self.overlap(graph, &layers, &crosses, &starts, horizontal) {
+                Some((layer, index, delta)) => {
+                    let shifted = if delta > 0.0 {
+                        &layers[layer][index..]
+                    } else {
+                        &layers[layer][..=index]
+                    };
+                    shifted.iter().for_each(|item| starts[*item] += delta);
+                }
+                None => break,
+            }
+        }
+        (0..graph.rank.len())
+            .map(|item| {
+                let (width, height) = sizes[item];
+                let along = mains[graph.rank[item]];
+                let across = starts[item] + crosses[item] / 2.0;
+                let (x, y) = match self.direction {
+                    Direction::LeftRight => (along, across),
+                    Direction::RightLeft => (-along, across),
+                    Direction::TopBottom => (across, along),
+                    Direction::BottomTop => (across, -along),
+                };
+                Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            })
+            .collect()
+    }
+
+    fn overlap(
+        &self,
+        graph: &Graph,
+        layers: &[Vec<usize>],
+        sizes: &[f64],
+        starts: &[f64],
+        horizontal: bool,
+    ) -> Option<(usize, usize, f64)> {
+        (0..self.clusters.len()).find_map(|cluster| {
+            let members: Vec<_> = (0..graph.rank.len())
+                .filter(|item| graph.paths[*item].contains(&cluster))
+                .collect();
+            let title = if horizontal {
+                text_size(&self.clusters[cluster].title).1
+            } else {
+                0.0
+            };
+            let low = members.iter().map(|item| starts[*item]).reduce(f64::min)?
+                - CLUSTER_PADDING
+                - title;
+            let high = members
+                .iter()
+                .map(|item| starts[*item] + sizes[*item])
+                .reduce(f64::max)?
+                + CLUSTER_PADDING;
+            let first = members.iter().map(|item| graph.rank[*item]).min()?;
+            let last = members.iter().map(|item| graph.rank[*item]).max()?;
+            (first..=last).find_map(|layer| {
+                let items = &layers[layer];
+                let positions: Vec<_> = items
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, item)| members.contains(item))
+                    .map(|(index, _)| index)
+                    .collect();
+                items.iter().enumerate().find_map(|(index, item)| {
+                    let (start, end) = (starts[*item], starts[*item] + sizes[*item]);
+                    if members.contains(item) || end <= low || start >= high {
+                        return None;
+                    }
+                    let after = match (positions.first(), positions.last()) {
+                        (Some(first), _) if index < *first => false,
+                        (_, Some(last)) if index > *last => true,
+                        _ => (start + end) / 2.0 > (low + high) / 2.0,
+                    };
+                    Some(if after {
+                        (layer, index, high + NODE_GAP - start)
+                    } else {
+                        (layer, index, low - NODE_GAP - end)
+                    })
+                })
+            })
+        })
+    }
+
+    fn cluster_rects(&self, graph: &Graph, items: &[Rect]) -> Vec<Option<Rect>> {
+        let mut rects: Vec<Option<Rect>> = vec![None; self.clusters.len()];
+        (0..self.clusters.len()).rev().for_each(|cluster| {
+            let members = graph
+                .paths
+                .iter()
+                .zip(items)
+                .filter(|(path, _)| path.last() == Some(&cluster))
+                .map(|(_, rect)| *rect);
+            let children = self
+                .clusters
+                .iter()
+                .zip(&rects)
+                .filter(|(child, _)| child.parent == Some(cluster))
+                .filter_map(|(_, rect)| *rect);
+            rects[cluster] = Rect::union(members.chain(children)).map(|rect| {
+                let title = text_size(&self.clusters[cluster].title).1;
+                Rect {
+                    x: rect.x,
+                    y: rect.y - title / 2.0,
+                    width: rect.width + 2.0 * CLUSTER_PADDING,
+                    height: rect.height + 2.0 * CLUSTER_PADDING + title,
+                }
+            });
+        });
+        rects
+    }
+
+    pub(crate) fn to_svg(&self) -> String {
+        let graph = self.graph();
+        let items = self.layout(&graph);
+        let clusters = self.cluster_rects(&graph, &items);
+        let nodes = &items[..self.nodes.len()];
+        let edges: Vec<_> = self
+            .edges
+            .iter()
+            .zip(&graph.routes)
+            .filter_map(|(edge, route)| route.as_ref().map(|route| (edge, route)))
+            .map(|(edge, route)| {
+                let mut points: Vec<_> = route
+                    .iter()
+                    .map(|item| (items[*item].x, items[*item].y))
+                    .collect();
+                let last = points.len() - 1;
+                points[0] = self.nodes[edge.from].clip(&nodes[edge.from], points[1].0, points[1].1);
+                points[last] = self.nodes[edge.to].clip(
+                    &nodes[edge.to],
+                    points[last - 1].0,
+                    points[last - 1].1,
+                );
+                let segment = label_segment(route);
+                let (width, height) = text_size(&edge.label);
+                let label = Rect {
+                    x: (points[segment].0 + points[segment + 1].0) / 2.0,
+                    y: (points[segment].1 + points[segment + 1].1) / 2.0,
+                    width: width + PADDING / 2.0,
+                    height,
+                };
+                (edge, points, label)
+            })
+            .collect();
+        let bounds = Rect::union(
+            nodes
+                .iter()
+                .copied()
+                .chain(clusters.iter().flatten().copied())
+                .chain(edges.iter().map(|(_, _, label)| *label)),
+        )
+        .unwrap_or_default();
+        let (dx, dy) = (MARGIN - bounds.left(), MARGIN - bounds.top());
+        let shift = |rect: &Rect| Rect {
+            x: rect.x + dx,
+            y: rect.y + dy,
+            ..*rect
+        };
+        let (width, height) = (bounds.width + 2.0 * MARGIN, bounds.height + 2.0 * MARGIN);
+        let cluster_rects: Vec<_> = clusters
+            .iter()
+            .zip(&self.clusters)
+            .filter_map(|(rect, cluster)| rect.as_ref().map(|rect| (shift(rect), cluster)))
+            .collect();
+        let edge_labels: Vec<_> = edges
+            .iter()
+            .filter(|(edge, _, _)| edge.label.iter().any(|line| !line.is_empty()))
+            .map(|(edge, _, label)| (shift(label), edge))
+            .collect();
+        [
+            format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width:.1}\" height=\"{height:.1}\" viewBox=\"0 0 {width:.1} {height:.1}\">\n"
+            ),
+            "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"9\" refY=\"5\" markerUnits=\"userSpaceOnUse\" markerWidth=\"12\" markerHeight=\"12\" orient=\"auto\"><path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#333333\"/></marker></defs>\n".to_string(),
+            format!("<rect width=\"{width:.1}\" height=\"{height:.1}\" fill=\"#ffffff\"/>\n"),
+            "<g fill=\"#ffffde\" stroke=\"#aaaa33\">\n".to_string(),
+            cluster_rects
+                .iter()
+                .map(|(rect, _)| {
+                    format!(
+                        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"/>\n",
+                        rect.left(),
+                        rect.top(),
+                        rect.width,
+                        rect.height
+                    )
+                })
+                .collect(),
+            "</g>\n<g fill=\"none\" stroke=\"#333333\" stroke-width=\"1.5\">\n".to_string(),
+            edges
+                .iter()
+                .map(|(edge, points, _)| {
+                    format!(
+                        "<path d=\"M {}\"{}{}/>\n",
+                        points
+                            .iter()
+                            .map(|(x, y)| format!("{:.1} {:.1}", x + dx, y + dy))
+                            .collect::<Vec<_>>()
+                            .join(" L "),
+                        match edge.stroke {
+                            Stroke::Normal => "",
+                            Stroke::Dotted => " stroke-dasharray=\"3 3\"",
+                            Stroke::Thick => " stroke-width=\"3\"",
+                        },
+                        if edge.arrow {
+                            " marker-end=\"url(#arrow)\""
+                        } else {
+                            ""
+                        }
+                    )
+                })
+                .collect(),
+            "</g>\n<g fill=\"#ececff\" stroke=\"#9370db\">\n".to_string(),
+            self.nodes
+                .iter()
+                .zip(nodes)
+                .map(|(node, rect)| node.draw(&shift(rect)))
+                .collect(),
+            "</g>\n<g fill=\"#e8e8e8\" stroke=\"none\">\n".to_string(),
+            edge_labels
+                .iter()
+                .map(|(rect, _)| {
+                    format!(
+                        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"/>\n",
+                        rect.left(),
+                        rect.top(),
+                        rect.width,
+                        rect.height
+                    )
+                })
+                .collect(),
+            "</g>\n<g font-family=\"sans-serif\" font-size=\"14\" fill=\"#333333\" text-anchor=\"middle\">\n".to_string(),
+            cluster_rects
+                .iter()
+                .map(|(rect, cluster)| {
+                    let height = text_size(&cluster.title).1;
+                    text(
+                        &cluster.title,
+                        rect.x,
+                        rect.top() + CLUSTER_PADDING / 2.0 + height / 2.0,
+                    )
+                })
+                .collect(),
+            self.nodes
+                .iter()
+                .zip(nodes)
+                .map(|(node, rect)| {
+                    let rect = shift(rect);
+                    text(&node.label, rect.x, rect.y)
+                })
+                .collect(),
+            edge_labels
+                .iter()
+                .map(|(rect, edge)| text(&edge.label, rect.x, rect.y))
+                .collect(),
+            "</g>\n</svg>\n".to_string(),
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Flowchart {
+        Flowchart::parse(text).unwrap()
+    }
+
+    fn rects(chart: &Flowchart) -> BTreeMap<&str, Rect> {
+        let items = chart.layout(&chart.graph());
+        chart
+            .ids
+            .iter()
+            .map(|(id, index)| (id.as_str(), items[*index]))
+            .collect()
+    }
+
+    fn edges(chart: &Flowchart) -> Vec<(&str, &str, Stroke, bool)> {
+        let ids: BTreeMap<_, _> = chart
+            .ids
+            .iter()
+            .map(|(id, index)| (*index, id.as_str()))
+            .collect();
+        chart
+            .edges
+            .iter()
+            .map(|edge| (ids[&edge.from], ids[&edge.to], edge.stroke, edge.arrow))
+            .collect()
+    }
+
+    #[test]
+    fn shapes() {
+        let chart = parse(
+            "flowchart TD
+            a[rect] --> b(round) --> c([stadium]) --> d[[subroutine]] --> e[(cylinder)]
+            f((circle)) --> g(((double))) --> h>asymmetric] --> i{rhombus} --> j{{hexagon}}
+            k",
+        );
+        let shapes: Vec<_> = chart
+            .ids
+            .values()
+            .map(|index| {
+                (
+                    chart.nodes[*index].label.join("\n"),
+                    chart.nodes[*index].shape,
+                )
+            })
+            .collect();
+        assert_eq!(
+            shapes,
+            [
+                ("rect".to_string(), Shape::Rect),
+                ("round".to_string(), Shape::Round),
+                ("stadium".to_string(), Shape::Stadium),
+                ("subroutine".to_string(), Shape::Subroutine),
+                ("cylinder".to_string(), Shape::Cylinder),
+                ("circle".to_string(), Shape::Circle),
+                ("double".to_string(), Shape::Circle),
+                ("asymmetric".to_string(), Shape::Asymmetric),
+                ("rhombus".to_string(), Shape::Rhombus),
+                ("hexagon".to_string(), Shape::Hexagon),
+                ("k".to_string(), Shape::Rect),
+            ]
+        );
+        let svg = chart.to_svg();
+        ["rect", "round", "stadium", "double", "hexagon"]
+            .iter()
+            .for_each(|label| assert!(svg.contains(&format!(">{label}<")), "{label}"));
+    }
+
+    #[test]
+    fn labels_and_strokes() {
+        let chart = parse(
+            "graph LR
+            a[\"quoted [label]\"] -->|yes| b
+            b -.-> c
+            c ==> d
+            d --- e
+            e -.- f;",
+        );
+        assert_eq!(chart.nodes[chart.ids["a"]].label, ["quoted [label]"]);
+        assert_eq!(chart.edges[0].label, ["yes"]);
+        assert_eq!(
+            edges(&chart),
+            [
+                ("a", "b", Stroke::Normal, true),
+                ("b", "c", Stroke::Dotted, true),
+                ("c", "d", Stroke::Thick, true),
+                ("d", "e", Stroke::Normal, false),
+                ("e", "f", Stroke::Dotted, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn directions() {
+        let rect = |direction| {
+            let chart = parse(&format!("flowchart {direction}\na --> b"));
+            let rects = rects(&chart);
+            (rects["a"], rects["b"])
+        };
+        let (a, b) = rect("LR");
+        assert!(a.right() < b.left() && a.y == b.y);
+        let (a, b) = rect("RL");
+        assert!(b.right() < a.left() && a.y == b.y);
+        let (a, b) = rect("TB");
+        assert!(a.bottom() < b.top() && a.x == b.x);
+        let (a, b) = rect("TD");
+        assert!(a.bottom() < b.top() && a.x == b.x);
+        let (a, b) = rect("BT");
+        assert!(b.bottom() < a.top() && a.x == b.x);
+    }
+
+    #[test]
+    fn cycles() {
+        let chart = parse(
+            "flowchart TD
+            a --> b --> c --> a
+            c --> d",
+        );
+        let graph = chart.graph();
+        assert_eq!(graph.rank[..chart.nodes.len()], [0, 1, 2, 3]);
+        assert!(graph.routes.iter().all(Option::is_some));
+        assert_eq!(chart.to_svg().matches("<path d=\"M ").count(), 5);
+    }
+
+    #[test]
+    fn nested_subgraphs() {
+        let chart = parse(
+            "flowchart LR
+            subgraph outer [Outer title]
+              a
+              subgraph inner
+                b
+              end
+            end
+            a --> b --> c",
+        );
+        assert_eq!(chart.clusters[0].title, ["Outer title"]);
+        assert_eq!(chart.clusters[0].parent, None);
+        assert_eq!(chart.clusters[1].title, ["inner"]);
+        assert_eq!(chart.clusters[1].parent, Some(0));
+        assert_eq!(chart.nodes[chart.ids["a"]].cluster, Some(0));
+        assert_eq!(chart.nodes[chart.ids["b"]].cluster, Some(1));
+        assert_eq!(chart.nodes[chart.ids["c"]].cluster, None);
+        let graph = chart.graph();
+        let items = chart.layout(&graph);
+        let clusters = chart.cluster_rects(&graph, &items);
+        let (outer, inner) = (clusters[0].unwrap(), clusters[1].unwrap());
+        assert!(outer.left() < inner.left() && inner.right() < outer.right());
+        assert!(outer.top() < inner.top() && inner.bottom() < outer.bottom());
+        let c = items[chart.ids["c"]];
+        assert!(outer.right() < c.left());
+    }
+
+    #[test]
+    fn rejected() {
+        [
+            "sequenceDiagram\na->>b: hi",
+            "flowchart XY\na --> b",
+            "flowchart LR TB\na --> b",
+            "flowchart LR\na -- text --> b",
+            "flowchart LR\ne & f --> g",
+            "flowchart LR\na --o b",
+            "flowchart LR\na --x b",
+            "flowchart LR\na <--> b",
+            "flowchart LR\na -> b",
+            "flowchart LR\nj[\"unterminated] --> k",
+            "flowchart LR\na[/parallelogram/] --> b",
+            "flowchart LR\nc --> c",
+            "flowchart LR\na --> b --> b",
+            "flowchart LR\na --> b\nend",
+            "flowchart LR\nsubgraph one\na --> b",
+            "flowchart LR\nsubgraph one [title] extra\na\nend",
+        ]
+        .iter()
+        .for_each(|text| assert!(Flowchart::parse(text).is_none(), "{text}"));
+    }
+}
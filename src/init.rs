@@ -0,0 +1,108 @@
+use {
+    crate::{Error, Result, CONFIG},
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+const OUTPUT: &str = "site";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Kit {
+    #[default]
+    C4,
+    Dataflow,
+}
+
+impl FromStr for Kit {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "c4" => Ok(Self::C4),
+            "dataflow" => Ok(Self::Dataflow),
+            _ => Err(format!("unknown kit {s:?} (c4, dataflow)")),
+        }
+    }
+}
+
+impl Kit {
+    fn files(self) -> [(&'static str, &'static str); 4] {
+        match self {
+            Self::C4 => [
+                (
+                    "model/relations.yaml",
+                    include_str!("../kits/c4/model/relations.yaml"),
+                ),
+                (
+                    "model/nodes.yaml",
+                    include_str!("../kits/c4/model/nodes.yaml"),
+                ),
+                (
+                    "model/diagrams.yaml",
+                    include_str!("../kits/c4/model/diagrams.yaml"),
+                ),
+                ("docs/README.md", include_str!("../kits/c4/docs/README.md")),
+            ],
+            Self::Dataflow => [
+                (
+                    "model/relations.yaml",
+                    include_str!("../kits/dataflow/model/relations.yaml"),
+                ),
+                (
+                    "model/nodes.yaml",
+                    include_str!("../kits/dataflow/model/nodes.yaml"),
+                ),
+                (
+                    "model/diagrams.yaml",
+                    include_str!("../kits/dataflow/model/diagrams.yaml"),
+                ),
+                (
+                    "docs/README.md",
+                    include_str!("../kits/dataflow/docs/README.md"),
+                ),
+            ],
+        }
+    }
+}
+
+pub fn init(dir: &Path, kit: Kit) -> Result<Vec<PathBuf>> {
+    let config = format!("model: model\ntemplate: docs\noutput: {OUTPUT}\n");
+    let files: Vec<_> = [(CONFIG, config.as_str())]
+        .into_iter()
+        .chain(kit.files())
+        .map(|(path, content)| (dir.join(path), content))
+        .collect();
+    if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+        return Err(Error::InitFileExists { path: path.clone() });
+    }
+    files.iter().try_for_each(|(path, content)| -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, content)?;
+        Ok(())
+    })?;
+    fs::create_dir_all(dir.join(OUTPUT))?;
+    let ignore = dir.join(".gitignore");
+    let entry = format!("/{OUTPUT}/");
+    let existing = match fs::read_to_string(&ignore) {
+        Ok(existing) => existing,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+    let ignored = existing.lines().any(|line| line.trim() == entry);
+    if !ignored {
+        let separator = if existing.is_empty() || existing.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        fs::write(&ignore, format!("{existing}{separator}{entry}\n"))?;
+    }
+    Ok(files
+        .into_iter()
+        .map(|(path, _)| path)
+        .chain((!ignored).then_some(ignore))
+        .collect())
+}
@@ -1,31 +1,217 @@
+mod api;
+mod asset;
+mod config;
+mod flowchart;
+mod init;
+mod manifest;
+mod markdown;
 mod model;
+mod namespace;
 mod node;
 mod output;
+mod page;
 mod relation;
 mod serve;
+mod stdlib;
+mod store;
+mod summary;
 mod watch;
 
-pub use crate::{model::Model, node::Node, relation::Relation};
+pub use crate::{
+    config::{Config, CONFIG},
+    init::{init, Kit},
+    model::Model,
+    node::Node,
+    relation::Relation,
+    summary::Summary,
+    watch::{watch, Watcher},
+};
 
-use std::{net::SocketAddr, path::Path};
+use {
+    serde::Deserialize,
+    std::{
+        collections::BTreeMap,
+        fs,
+        net::SocketAddr,
+        path::{Path, PathBuf},
+        process::{Command, ExitStatus},
+        str::FromStr,
+        sync::Arc,
+        time::Duration,
+    },
+};
 
-pub fn run_one_time(model: &Path, template: &Path, output: &Path) -> Result<()> {
-    output::build(model, template, output)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    #[default]
+    Strict,
+    Lenient,
 }
 
-pub async fn run_serve(
-    model: &Path,
-    template: &Path,
+impl FromStr for Strictness {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "lenient" => Ok(Self::Lenient),
+            _ => Err(format!("unknown strictness {s:?} (strict, lenient)")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub debounce: Option<Duration>,
+    pub poll: Option<Duration>,
+    pub strictness: Strictness,
+    pub packages: BTreeMap<String, PathBuf>,
+    pub extensions: Vec<String>,
+    pub banner: bool,
+    pub export: Option<PathBuf>,
+    pub svg: Option<PathBuf>,
+    pub svg_links: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            include: vec![],
+            exclude: vec![],
+            debounce: None,
+            poll: None,
+            strictness: Strictness::default(),
+            packages: BTreeMap::new(),
+            extensions: vec!["md".to_string()],
+            banner: true,
+            export: None,
+            svg: None,
+            svg_links: false,
+        }
+    }
+}
+
+fn canonicalize(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    paths
+        .iter()
+        .map(|path| fs::canonicalize(path).map_err(|err| err.into()))
+        .collect()
+}
+
+pub fn run_one_time(
+    models: &[PathBuf],
+    templates: &[PathBuf],
     output: &Path,
+    options: &Options,
+) -> Result<Summary> {
+    output::build(models, templates, output, options)
+}
+
+pub async fn run_serve(
+    models: &[PathBuf],
+    templates: &[PathBuf],
+    output: Option<&Path>,
     addr: &SocketAddr,
+    options: &Options,
+) -> Result<()> {
+    let models = canonicalize(models)?;
+    let templates = canonicalize(templates)?;
+    let output = output.map(fs::canonicalize).transpose()?;
+    let live = serve::Live::new();
+    let api = api::Api::default();
+    let (mut store, source) = match &output {
+        Some(output) => (
+            store::Store::Dir(output.clone()),
+            serve::Source::Dir(output.clone()),
+        ),
+        None => {
+            let shared = store::Shared::default();
+            (
+                store::Store::memory(Arc::clone(&shared)),
+                serve::Source::Memory(shared),
+            )
+        }
+    };
+    let watcher = watch(&models, &templates, output.as_deref(), options, {
+        let models = models.clone();
+        let templates = templates.clone();
+        let options = options.clone();
+        let live = live.clone();
+        let api = api.clone();
+        let mut state = output::State::default();
+        move |changed| match state.build(&models, &templates, &mut store, &options, changed) {
+            Ok(summary) => {
+                api.succeeded(state.resolved(), state.diagrams());
+                live.succeeded(
+                    summary
+                        .changed()
+                        .filter_map(|path| path.to_str())
+                        .map(|path| format!("/{}", path.replace('\\', "/")))
+                        .collect(),
+                );
+                Ok(state.model_files().iter().cloned().collect())
+            }
+            Err(err) => {
+                api.failed(&err);
+                live.failed(&err);
+                Err(err)
+            }
+        }
+    })?;
+    let result = serve::serve(source, addr, live, api).await;
+    drop(watcher);
+    result
+}
+
+fn run_command(command: &str) -> Result<()> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let status = Command::new(shell).arg(flag).arg(command).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::CommandFailed {
+            command: command.to_string(),
+            status,
+        })
+    }
+}
+
+pub async fn run_watch(
+    models: &[PathBuf],
+    templates: &[PathBuf],
+    output: &Path,
+    options: &Options,
+    command: Option<&str>,
 ) -> Result<()> {
-    watch::watch(model, template, output, {
-        let model = model.to_path_buf();
-        let template = template.to_path_buf();
-        let output = output.to_path_buf();
-        move || output::build(&model, &template, &output)
-    });
-    serve::serve(output, addr).await
+    let models = canonicalize(models)?;
+    let templates = canonicalize(templates)?;
+    let output = fs::canonicalize(output)?;
+    let watcher = watch(&models, &templates, Some(&output), options, {
+        let models = models.clone();
+        let templates = templates.clone();
+        let mut store = store::Store::Dir(output.clone());
+        let options = options.clone();
+        let command = command.map(|command| command.to_string());
+        let mut state = output::State::default();
+        move |changed| {
+            state.build(&models, &templates, &mut store, &options, changed)?;
+            command
+                .iter()
+                .try_for_each(|command| run_command(command))?;
+            Ok(state.model_files().iter().cloned().collect())
+        }
+    })?;
+    println!("watching {models:?} and {templates:?}");
+    serve::shutdown().await;
+    drop(watcher);
+    Ok(())
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -36,13 +222,70 @@ pub enum Error {
     NodeHasUnknownParent { list: Vec<(String, String)> },
 
     #[error("node has no definition: {0:?}")]
-    NodeHasNoDefinition(Node),
+    NodeHasNoDefinition(Box<Node>),
+
+    #[error(
+        "node id contains {}, reserved for namespaces in diagrams: {list:?}",
+        namespace::MANGLED
+    )]
+    NodeHasReservedId { list: Vec<String> },
 
     #[error("node relation has unknown parent (node, relation, parent): {list:?}")]
     NodeRelationHasUnknownParent { list: Vec<(String, String, String)> },
 
     #[error("relation has no definition: {0:?}")]
-    RelationHasNoDefinition(Relation),
+    RelationHasNoDefinition(Box<Relation>),
+
+    #[error("invalid config {path:?}: {source}")]
+    Config {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[error("missing {0}: pass it as an argument or set it in {CONFIG}")]
+    ConfigMissing(&'static str),
+
+    #[error("init would overwrite existing file: {path:?}")]
+    InitFileExists { path: PathBuf },
+
+    #[error("model has import cycle: {list:?}")]
+    ModelImportCycle { list: Vec<PathBuf> },
+
+    #[error("model has unknown package (file, package): {path:?}, {package:?}")]
+    ModelHasUnknownPackage { path: PathBuf, package: String },
+
+    #[error("model has unknown library (file, library): {path:?}, {library:?}")]
+    ModelHasUnknownLibrary { path: PathBuf, library: String },
+
+    #[error("model file has unsupported format: {path:?}")]
+    ModelHasUnsupportedFormat { path: PathBuf },
+
+    #[error("page has invalid front matter {path:?}: {source}")]
+    PageFrontMatter {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[error("page has invalid output path (page, output): {path:?}, {output:?}")]
+    PageHasInvalidOutput { path: PathBuf, output: PathBuf },
+
+    #[error("page has unknown diagrams (page, diagrams): {path:?}, {list:?}")]
+    PageHasUnknownDiagram { path: PathBuf, list: Vec<String> },
+
+    #[error("page has unknown layout (page, layout): {path:?}, {layout:?}")]
+    PageHasUnknownLayout { path: PathBuf, layout: String },
+
+    #[error("diagram has invalid definition {diagram:?}: {source}")]
+    DiagramRender {
+        diagram: String,
+        source: Box<handlebars::RenderError>,
+    },
+
+    #[error("diagram export has invalid path: {path:?}")]
+    ExportHasInvalidPath { path: PathBuf },
+
+    #[error("command failed (command, status): {command:?}, {status}")]
+    CommandFailed { command: String, status: ExitStatus },
 
     #[error("render error: {source:?}")]
     RenderError {
@@ -62,6 +305,30 @@ pub enum Error {
         source: serde_yaml::Error,
     },
 
+    #[error("toml error: {source:?}")]
+    Toml {
+        #[from]
+        source: toml::de::Error,
+    },
+
+    #[error("glob error: {source:?}")]
+    Glob {
+        #[from]
+        source: globset::Error,
+    },
+
+    #[error("json error: {source:?}")]
+    Json {
+        #[from]
+        source: serde_json::Error,
+    },
+
+    #[error("notify error: {source:?}")]
+    Notify {
+        #[from]
+        source: notify::Error,
+    },
+
     #[error("hyper error: {source:?}")]
     Hyper {
         #[from]
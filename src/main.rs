@@ -1,30 +1,149 @@
 use {
-    clap::Parser,
-    std::{env, net::SocketAddr, path::PathBuf},
+    clap::{Parser, Subcommand},
+    std::{env, net::SocketAddr, path::PathBuf, process},
+    symo::{Config, Error, Kit, Strictness},
 };
 
+#[derive(Subcommand)]
+enum Command {
+    Init {
+        #[clap(default_value = ".")]
+        dir: PathBuf,
+
+        #[clap(long, default_value = "c4")]
+        kit: Kit,
+    },
+}
+
 #[derive(Parser)]
-#[clap(about, version)]
+#[clap(about, version, args_conflicts_with_subcommands = true)]
 struct Args {
-    model: PathBuf,
-    template: PathBuf,
-    output: PathBuf,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    model: Option<PathBuf>,
+    template: Option<PathBuf>,
+    output: Option<PathBuf>,
+
+    #[clap(short, long)]
+    config: Option<PathBuf>,
 
     #[clap(short, long)]
     serve: bool,
 
-    #[clap(short, long, default_value = "127.0.0.1:0")]
-    addr: SocketAddr,
+    #[clap(short, long, conflicts_with = "serve")]
+    watch: bool,
+
+    #[clap(long, requires = "watch")]
+    exec: Option<String>,
+
+    #[clap(short, long)]
+    addr: Option<SocketAddr>,
+
+    #[clap(long)]
+    include: Vec<String>,
+
+    #[clap(long)]
+    exclude: Vec<String>,
+
+    #[clap(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    debounce: Option<u64>,
+
+    #[clap(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    poll: Option<u64>,
+
+    #[clap(long)]
+    strictness: Option<Strictness>,
+
+    #[clap(long = "extension", value_name = "EXT")]
+    extensions: Vec<String>,
+
+    #[clap(long)]
+    no_banner: bool,
+
+    #[clap(long, value_name = "DIR")]
+    export: Option<PathBuf>,
+
+    #[clap(long, value_name = "DIR")]
+    svg: Option<PathBuf>,
+
+    #[clap(long, requires = "svg")]
+    svg_links: bool,
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Args::parse_from(env::args_os());
+async fn run(args: Args) -> symo::Result<()> {
+    if let Some(Command::Init { dir, kit }) = &args.command {
+        symo::init(dir, *kit)?
+            .iter()
+            .for_each(|path| println!("created {}", path.display()));
+        return Ok(());
+    }
+    let file = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::discover(&env::current_dir()?)?.unwrap_or_default(),
+    };
+    let config = Config {
+        model: args
+            .model
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default(),
+        template: args
+            .template
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default(),
+        output: args.output,
+        addr: args.addr,
+        include: args.include,
+        exclude: args.exclude,
+        debounce: args.debounce,
+        poll: args.poll,
+        strictness: args.strictness,
+        extensions: args.extensions,
+        banner: args.no_banner.then_some(false),
+        export: args.export,
+        svg: args.svg,
+        svg_links: args.svg_links.then_some(true),
+        ..Config::default()
+    }
+    .or(file);
+    let options = config.options();
+    if config.model.is_empty() {
+        return Err(Error::ConfigMissing("model"));
+    }
+    if config.template.is_empty() {
+        return Err(Error::ConfigMissing("template"));
+    }
+    let output = config.output.ok_or(Error::ConfigMissing("output"));
 
     if args.serve {
-        symo::run_serve(&args.model, &args.template, &args.output, &args.addr).await
+        symo::run_serve(
+            &config.model,
+            &config.template,
+            output.ok().as_deref(),
+            &config
+                .addr
+                .unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 0))),
+            &options,
+        )
+        .await
+    } else if args.watch {
+        symo::run_watch(
+            &config.model,
+            &config.template,
+            &output?,
+            &options,
+            args.exec.as_deref(),
+        )
+        .await
     } else {
-        symo::run_one_time(&args.model, &args.template, &args.output)
+        symo::run_one_time(&config.model, &config.template, &output?, &options).map(|_| ())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run(Args::parse_from(env::args_os())).await {
+        eprintln!("error: {err}");
+        process::exit(1);
     }
-    .unwrap()
 }
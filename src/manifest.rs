@@ -0,0 +1,54 @@
+use {
+    crate::{summary::Summary, Result},
+    std::{
+        collections::BTreeSet,
+        fs,
+        io::ErrorKind,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+pub(crate) const MANIFEST: &str = ".symo-manifest";
+
+fn read(output: &Path) -> Result<BTreeSet<PathBuf>> {
+    match fs::read_to_string(output.join(MANIFEST)) {
+        Ok(content) => Ok(content
+            .lines()
+            .map(PathBuf::from)
+            .filter(|path| {
+                path.components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+            })
+            .collect()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(BTreeSet::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub(crate) fn update(
+    output: &Path,
+    generated: &BTreeSet<PathBuf>,
+    summary: &mut Summary,
+) -> Result<()> {
+    read(output)?
+        .difference(generated)
+        .filter(|path| output.join(path).is_file())
+        .try_for_each(|path| -> Result<()> {
+            fs::remove_file(output.join(path))?;
+            summary.removed.push(path.clone());
+            path.ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .take_while(|dir| fs::remove_dir(output.join(dir)).is_ok())
+                .for_each(drop);
+            Ok(())
+        })?;
+    fs::write(
+        output.join(MANIFEST),
+        generated
+            .iter()
+            .filter_map(|path| path.to_str())
+            .fold(String::new(), |acc, path| format!("{acc}{path}\n")),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use {
+    pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd},
+    std::iter,
+};
+
+pub(crate) const MERMAID: &str = "/_symo/mermaid.min.js";
+
+const STYLE: &str =
+    "body { max-width: 60em; margin: 0 auto; padding: 1em; font-family: sans-serif }
+table { border-collapse: collapse }
+th, td { border: 1px solid #d0d7de; padding: 0.3em 0.6em }
+pre { background: #f6f8fa; padding: 1em; overflow: auto }
+pre.mermaid { background: none }";
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.chars().fold(String::new(), |mut acc, c| {
+        match c {
+            '&' => acc.push_str("&amp;"),
+            '<' => acc.push_str("&lt;"),
+            '>' => acc.push_str("&gt;"),
+            '"' => acc.push_str("&quot;"),
+            '\'' => acc.push_str("&#39;"),
+            c => acc.push(c),
+        }
+        acc
+    })
+}
+
+pub(crate) fn strip_fence(diagram: &str) -> &str {
+    let trimmed = diagram.trim();
+    trimmed
+        .strip_prefix("```mermaid")
+        .and_then(|rest| rest.strip_suffix("```"))
+        .map(|rest| rest.trim_matches(['\r', '\n']))
+        .unwrap_or(trimmed)
+}
+
+pub(crate) fn to_html(markdown: &str) -> String {
+    let mut mermaid: Option<String> = None;
+    let events = Parser::new_ext(
+        markdown,
+        Options::ENABLE_GFM
+            | Options::ENABLE_TABLES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_FOOTNOTES,
+    )
+    .filter_map(|event| match (&mut mermaid, event) {
+        (None, Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(lang))))
+            if lang.as_ref() == "mermaid" =>
+        {
+            mermaid = Some(String::new());
+            None
+        }
+        (Some(_), Event::End(TagEnd::CodeBlock)) => Some(Event::Html(
+            format!(
+                "<pre class=\"mermaid\">{}</pre>\n",
+                escape_html(&mermaid.take().unwrap())
+            )
+            .into(),
+        )),
+        (Some(diagram), Event::Text(text)) => {
+            diagram.push_str(&text);
+            None
+        }
+        (_, event) => Some(event),
+    });
+    let mut content = String::new();
+    html::push_html(&mut content, events);
+    content
+}
+
+pub(crate) fn page(title: &str, content: &str) -> String {
+    iter::once(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n",
+        escape_html(title)
+    ))
+    .chain(iter::once(content.to_string()))
+    .chain(iter::once(format!(
+        "<script src=\"{MERMAID}\"></script>\n<script>mermaid.initialize({{ startOnLoad: true }});</script>\n</body>\n</html>\n"
+    )))
+    .collect()
+}
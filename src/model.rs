@@ -1,16 +1,21 @@
 use {
-    crate::{node::Node, output::Merge, relation::Relation, Error, Result},
+    crate::{
+        namespace::{self, Resolver},
+        node::Node,
+        output::Merge,
+        relation::Relation,
+        stdlib, Error, Result,
+    },
     serde::{Deserialize, Serialize},
     std::{
-        collections::{BTreeMap, HashSet},
-        ffi::OsString,
-        fs::File,
-        path::Path,
+        collections::{BTreeMap, BTreeSet, HashSet},
+        fs::{self, File},
+        path::{Path, PathBuf},
     },
     walkdir::WalkDir,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Model {
     #[serde(default)]
     pub relations: BTreeMap<String, Relation>,
@@ -64,28 +69,187 @@ fn merge<T: Merge>(map: &mut BTreeMap<String, T>) -> Result<()> {
     Ok(())
 }
 
-impl Model {
-    pub fn new(path: &Path) -> Result<Self> {
-        let mut model: Self = WalkDir::new(path)
+#[derive(Deserialize)]
+struct ModelFile {
+    #[serde(default)]
+    imports: Vec<String>,
+    namespace: Option<String>,
+    #[serde(flatten)]
+    model: Model,
+}
+
+const EXTENSIONS: [&str; 4] = ["yaml", "yml", "json", "toml"];
+
+fn parse(path: &Path) -> Result<ModelFile> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("yaml" | "yml") => Ok(serde_yaml::from_reader(File::open(path)?)?),
+        Some("json") => Ok(serde_json::from_reader(File::open(path)?)?),
+        Some("toml") => Ok(toml::from_str(&fs::read_to_string(path)?)?),
+        _ => Err(Error::ModelHasUnsupportedFormat {
+            path: path.to_path_buf(),
+        }),
+    }
+}
+
+struct Loader<'a> {
+    packages: &'a BTreeMap<String, PathBuf>,
+    stack: Vec<PathBuf>,
+    files: BTreeSet<PathBuf>,
+    libraries: BTreeMap<&'static str, Model>,
+    parts: Vec<(String, Model)>,
+}
+
+impl Loader<'_> {
+    fn load_dir(&mut self, path: &Path) -> Result<()> {
+        WalkDir::new(path)
+            .sort_by_file_name()
             .into_iter()
             .filter_map(|item| item.ok())
             .filter(|item| item.file_type().is_file())
-            .filter(|item| item.path().extension().unwrap_or(&OsString::new()) == "yaml")
-            .map(|item| item.into_path())
-            .try_fold(
-                Self {
-                    relations: BTreeMap::new(),
-                    nodes: BTreeMap::new(),
-                    diagrams: BTreeMap::new(),
-                },
-                |mut acc, item| -> Result<Model> {
-                    let mut model: Self = serde_yaml::from_reader(File::open(item)?)?;
-                    acc.relations.append(&mut model.relations);
-                    acc.nodes.append(&mut model.nodes);
-                    acc.diagrams.append(&mut model.diagrams);
-                    Ok(acc)
-                },
-            )?;
+            .filter(|item| {
+                let supported = item
+                    .path()
+                    .extension()
+                    .and_then(|extension| extension.to_str())
+                    .map(|extension| EXTENSIONS.contains(&extension))
+                    .unwrap_or(false);
+                let hidden = item.file_name().to_string_lossy().starts_with('.');
+                if !supported && !hidden {
+                    eprintln!(
+                        "warning: skipping {:?}, model files must have one of the extensions {EXTENSIONS:?}",
+                        item.path()
+                    );
+                }
+                supported
+            })
+            .try_for_each(|item| {
+                let namespace = item
+                    .path()
+                    .parent()
+                    .unwrap()
+                    .strip_prefix(path)
+                    .unwrap()
+                    .components()
+                    .filter_map(|component| component.as_os_str().to_str())
+                    .collect::<Vec<_>>()
+                    .join(namespace::SEPARATOR);
+                self.load_file(item.path(), namespace)
+            })
+    }
+
+    fn load_file(&mut self, path: &Path, namespace: String) -> Result<()> {
+        let path = path.canonicalize()?;
+        if self.stack.contains(&path) {
+            return Err(Error::ModelImportCycle {
+                list: self.stack.iter().cloned().chain([path]).collect(),
+            });
+        }
+        if self.files.contains(&path) {
+            return Ok(());
+        }
+        let file = parse(&path)?;
+        self.stack.push(path.clone());
+        file.imports
+            .iter()
+            .try_for_each(|import| self.import(&path, import))?;
+        self.stack.pop();
+        self.parts
+            .push((file.namespace.unwrap_or(namespace), file.model));
+        self.files.insert(path);
+        Ok(())
+    }
+
+    fn load_library(&mut self, from: &Path, name: &str) -> Result<()> {
+        let name = name.strip_suffix(".yaml").unwrap_or(name);
+        let libraries: Vec<_> = stdlib::LIBRARIES
+            .iter()
+            .filter(|(library, _)| name.is_empty() || *library == name)
+            .collect();
+        if libraries.is_empty() {
+            return Err(Error::ModelHasUnknownLibrary {
+                path: from.to_path_buf(),
+                library: name.to_string(),
+            });
+        }
+        libraries.into_iter().try_for_each(|(library, content)| {
+            if !self.libraries.contains_key(library) {
+                let file: ModelFile = serde_yaml::from_str(content)?;
+                self.libraries.insert(library, file.model);
+            }
+            Ok(())
+        })
+    }
+
+    fn import(&mut self, from: &Path, import: &str) -> Result<()> {
+        let path = if let Some(import) = import.strip_prefix('@') {
+            let (name, rest) = import.split_once('/').unwrap_or((import, ""));
+            if name == stdlib::PACKAGE && !self.packages.contains_key(name) {
+                return self.load_library(from, rest);
+            }
+            self.packages
+                .get(name)
+                .ok_or_else(|| Error::ModelHasUnknownPackage {
+                    path: from.to_path_buf(),
+                    package: name.to_string(),
+                })?
+                .join(rest)
+        } else {
+            from.parent().unwrap().join(import)
+        };
+        if path.is_dir() {
+            self.load_dir(&path)
+        } else {
+            self.load_file(&path, String::new())
+        }
+    }
+}
+
+impl Model {
+    pub fn new(path: &Path) -> Result<Self> {
+        Self::layered(&[path.to_path_buf()])
+    }
+
+    pub fn layered(paths: &[PathBuf]) -> Result<Self> {
+        Self::load(paths, &BTreeMap::new()).map(|(model, _)| model)
+    }
+
+    pub(crate) fn load(
+        paths: &[PathBuf],
+        packages: &BTreeMap<String, PathBuf>,
+    ) -> Result<(Self, BTreeSet<PathBuf>)> {
+        let mut loader = Loader {
+            packages,
+            stack: vec![],
+            files: BTreeSet::new(),
+            libraries: BTreeMap::new(),
+            parts: vec![],
+        };
+        paths.iter().try_for_each(|path| loader.load_dir(path))?;
+        let parts: Vec<_> = loader
+            .libraries
+            .into_values()
+            .map(|model| (String::new(), model))
+            .chain(loader.parts)
+            .collect();
+        let resolver = Resolver::new(parts.iter());
+        let mut model = parts
+            .into_iter()
+            .fold(Self::default(), |mut acc, (namespace, model)| {
+                let mut model = resolver.apply(&namespace, model);
+                acc.relations.append(&mut model.relations);
+                acc.nodes.append(&mut model.nodes);
+                acc.diagrams.append(&mut model.diagrams);
+                acc
+            });
+        let reserved: Vec<_> = model
+            .nodes
+            .keys()
+            .filter(|id| id.contains(namespace::MANGLED))
+            .cloned()
+            .collect();
+        if !reserved.is_empty() {
+            return Err(Error::NodeHasReservedId { list: reserved });
+        }
         merge(&mut model.relations)?;
         merge(&mut model.nodes)?;
         model.nodes = model
@@ -107,7 +271,7 @@ impl Model {
                 (id, node)
             })
             .collect();
-        Ok(model)
+        Ok((model, loader.files))
     }
 
     pub(crate) fn diagram_definitions(
@@ -0,0 +1,140 @@
+use {
+    crate::{model::Model, relation::Relation},
+    std::collections::{BTreeMap, BTreeSet},
+};
+
+pub(crate) const SEPARATOR: &str = "::";
+
+pub(crate) const MANGLED: &str = "__";
+
+pub(crate) fn qualify(namespace: &str, id: &str) -> String {
+    if namespace.is_empty() || id.contains(SEPARATOR) {
+        id.to_string()
+    } else {
+        format!("{namespace}{SEPARATOR}{id}")
+    }
+}
+
+pub(crate) fn mangle(id: &str) -> String {
+    id.replace(SEPARATOR, MANGLED)
+}
+
+fn map_lines(text: &str, map: impl Fn(&str) -> Option<String>) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let trimmed = line.trim();
+            match map(trimmed).filter(|_| !trimmed.is_empty()) {
+                Some(id) => {
+                    let start = line.find(trimmed).unwrap();
+                    format!("{}{}{}", &line[..start], id, &line[start + trimmed.len()..])
+                }
+                None => line.to_string(),
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn mangle_lines<T>(text: &str, nodes: &BTreeMap<String, T>) -> String {
+    map_lines(text, |id| {
+        (id.contains(SEPARATOR) && nodes.contains_key(id)).then(|| mangle(id))
+    })
+}
+
+pub(crate) struct Resolver {
+    nodes: BTreeSet<String>,
+    relations: BTreeSet<String>,
+}
+
+fn resolve(keys: &BTreeSet<String>, namespace: &str, id: &str) -> String {
+    let qualified = qualify(namespace, id);
+    if keys.contains(&qualified) {
+        qualified
+    } else {
+        id.to_string()
+    }
+}
+
+impl Resolver {
+    pub(crate) fn new<'a>(parts: impl Iterator<Item = &'a (String, Model)>) -> Self {
+        let (nodes, relations): (Vec<_>, Vec<_>) = parts
+            .map(|(namespace, model)| {
+                (
+                    model
+                        .nodes
+                        .keys()
+                        .map(|id| qualify(namespace, id))
+                        .collect::<Vec<_>>(),
+                    model
+                        .relations
+                        .keys()
+                        .map(|id| qualify(namespace, id))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .unzip();
+        Self {
+            nodes: nodes.into_iter().flatten().collect(),
+            relations: relations.into_iter().flatten().collect(),
+        }
+    }
+
+    fn relation(&self, namespace: &str, relation: Relation) -> Relation {
+        let node = |id: Option<String>| id.map(|id| resolve(&self.nodes, namespace, &id));
+        Relation {
+            parent: relation
+                .parent
+                .map(|id| resolve(&self.relations, namespace, &id)),
+            left: node(relation.left),
+            right: node(relation.right),
+            ..relation
+        }
+    }
+
+    pub(crate) fn apply(&self, namespace: &str, model: Model) -> Model {
+        if namespace.is_empty() {
+            return model;
+        }
+        Model {
+            relations: model
+                .relations
+                .into_iter()
+                .map(|(id, relation)| (qualify(namespace, &id), self.relation(namespace, relation)))
+                .collect(),
+            nodes: model
+                .nodes
+                .into_iter()
+                .map(|(id, mut node)| {
+                    node.parent = node.parent.map(|id| resolve(&self.nodes, namespace, &id));
+                    node.relations = node.relations.map(|relations| {
+                        relations
+                            .into_iter()
+                            .map(|(id, relations)| {
+                                (
+                                    resolve(&self.nodes, namespace, &id),
+                                    relations
+                                        .into_iter()
+                                        .map(|relation| self.relation(namespace, relation))
+                                        .collect(),
+                                )
+                            })
+                            .collect()
+                    });
+                    (qualify(namespace, &id), node)
+                })
+                .collect(),
+            diagrams: model
+                .diagrams
+                .into_iter()
+                .map(|(name, diagram)| {
+                    (
+                        qualify(namespace, &name),
+                        map_lines(&diagram, |id| {
+                            let qualified = qualify(namespace, id);
+                            self.nodes.contains(&qualified).then_some(qualified)
+                        }),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
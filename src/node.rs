@@ -1,11 +1,12 @@
 use {
-    crate::{model::Model, output::Merge, relation::Relation, Error, Result},
+    crate::{model::Model, namespace, output::Merge, relation::Relation, Error, Result},
     handlebars::Handlebars,
     serde::{Deserialize, Serialize},
+    serde_json::Value,
     std::collections::BTreeMap,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Node {
     pub id: Option<String>,
     pub parent: Option<String>,
@@ -107,14 +108,18 @@ impl Node {
     }
 
     pub(crate) fn render_definition(&mut self, handlebars: &Handlebars) -> Result<()> {
+        let mut context = serde_json::to_value(&*self)?;
+        if let Some(id) = &self.id {
+            context["id"] = Value::String(namespace::mangle(id));
+        }
         self.definition = Some(
             handlebars.render_template(
                 &self
                     .definition
                     .as_ref()
-                    .ok_or_else(|| Error::NodeHasNoDefinition(self.clone()))?
+                    .ok_or_else(|| Error::NodeHasNoDefinition(Box::new(self.clone())))?
                     .clone(),
-                self,
+                &context,
             )?,
         );
         Ok(())
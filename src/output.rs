@@ -1,27 +1,156 @@
 use {
-    crate::{model::Model, Result},
+    crate::{
+        asset::Filter,
+        flowchart::Flowchart,
+        markdown,
+        model::Model,
+        namespace,
+        page::{Page, PageInfo},
+        store::Store,
+        summary::Summary,
+        Error, Options, Result, Strictness,
+    },
     handlebars::{Context, Handlebars, Helper, HelperResult, Output, RenderContext},
-    serde::Serialize,
+    serde_json::Value,
     std::{
-        collections::{BTreeMap, HashSet},
-        ffi::OsString,
-        fs::{self, File},
-        io::Write,
-        iter,
-        path::Path,
+        collections::{BTreeMap, BTreeSet, HashSet},
+        fs, iter,
+        path::{Component, Path, PathBuf},
         sync::Arc,
     },
     walkdir::WalkDir,
 };
 
+const LAYOUTS: &str = "_layouts";
+const TEMPLATE: &str = "hbs";
+const BANNER: &str = "DO NOT EDIT; Autogenerated";
+const XML_DECLARATION: &str = "<?xml";
+
+fn is_template(path: &Path, options: &Options) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| {
+            extension == TEMPLATE
+                || options
+                    .extensions
+                    .iter()
+                    .any(|allowed| allowed.trim_start_matches('.') == extension)
+        })
+        .unwrap_or(false)
+}
+
+fn output_path(path: &Path) -> PathBuf {
+    if path.extension().map(|extension| extension == TEMPLATE) == Some(true) {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn with_banner(path: &Path, content: String, options: &Options) -> String {
+    let banner = match banner(path).filter(|_| options.banner) {
+        Some(banner) => banner,
+        None => return content,
+    };
+    match content
+        .find("?>")
+        .filter(|_| content.starts_with(XML_DECLARATION))
+    {
+        Some(end) => {
+            let (declaration, rest) = content.split_at(end + 2);
+            let rest = rest.strip_prefix('\n').unwrap_or(rest);
+            format!("{declaration}\n{banner}\n{rest}")
+        }
+        None => format!("{banner}\n\n{content}"),
+    }
+}
+
+fn export_dir(dir: Option<&Path>) -> Result<Option<&Path>> {
+    match dir {
+        Some(dir)
+            if !dir
+                .components()
+                .all(|component| matches!(component, Component::Normal(_))) =>
+        {
+            Err(Error::ExportHasInvalidPath {
+                path: dir.to_path_buf(),
+            })
+        }
+        dir => Ok(dir),
+    }
+}
+
+fn export_path(dir: &Path, name: &str, extension: &str) -> Result<PathBuf> {
+    let path = dir.join(name.replace(namespace::SEPARATOR, "/"));
+    let relative = path.strip_prefix(dir).unwrap_or(&path);
+    if relative.as_os_str().is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(Error::ExportHasInvalidPath { path });
+    }
+    let mut path = path.into_os_string();
+    path.push(format!(".{extension}"));
+    Ok(path.into())
+}
+
+fn reference(page: &Path, name: &str, target: &Path) -> String {
+    let src = page
+        .parent()
+        .into_iter()
+        .flat_map(|dir| dir.components())
+        .map(|_| "..".to_string())
+        .chain(
+            target
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>()
+        .join("/");
+    match page.extension().and_then(|extension| extension.to_str()) {
+        Some("html" | "htm") => format!("<img src=\"{src}\" alt=\"{name}\">"),
+        Some("adoc" | "asciidoc") => format!("image::{src}[{name}]"),
+        Some("rst") => format!(".. image:: {src}"),
+        _ => format!("![{name}]({src})"),
+    }
+}
+
+fn linked(
+    diagrams: &BTreeMap<String, String>,
+    links: &BTreeMap<&String, &PathBuf>,
+    page: &Path,
+) -> BTreeMap<String, String> {
+    diagrams
+        .iter()
+        .map(|(name, diagram)| match links.get(name) {
+            Some(target) => (name.clone(), reference(page, name, target)),
+            None => (name.clone(), diagram.clone()),
+        })
+        .collect()
+}
+
+fn banner(path: &Path) -> Option<String> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("md" | "markdown" | "html" | "htm" | "xml" | "svg") => {
+            Some(format!("<!-- {BANNER} -->"))
+        }
+        Some("adoc" | "asciidoc") => Some(format!("// {BANNER}")),
+        Some("rst") => Some(format!(".. {BANNER}")),
+        Some("mmd") => Some(format!("%% {BANNER}")),
+        Some("yaml" | "yml" | "toml") => Some(format!("# {BANNER}")),
+        _ => None,
+    }
+}
+
 pub(crate) trait Merge: Clone {
     fn parent(&self) -> Option<String>;
     fn merge(&mut self, parent: &Self);
 }
 
-fn new_handlebars<'a>() -> Handlebars<'a> {
+fn new_handlebars<'a>(options: &Options) -> Handlebars<'a> {
     let mut handlebars = Handlebars::new();
-    handlebars.set_strict_mode(true);
+    handlebars.set_strict_mode(options.strictness == Strictness::Strict);
     handlebars
 }
 
@@ -43,14 +172,18 @@ fn render_nodes_relations_definitions(handlebars: &Handlebars, model: &mut Model
         .try_for_each(|relation| relation.render_definition(handlebars))
 }
 
-fn render_diagrams(
-    mut handlebars: Handlebars,
-    mut model: Model,
-) -> Result<BTreeMap<String, String>> {
+fn resolve(mut model: Model, options: &Options) -> Result<Model> {
+    let handlebars = new_handlebars(options);
     render_nodes_definitions(&handlebars, &mut model)?;
     render_nodes_relations_definitions(&handlebars, &mut model)?;
+    Ok(model)
+}
 
-    let model = Arc::new(model);
+fn render_diagrams(
+    mut handlebars: Handlebars,
+    model: Arc<Model>,
+    names: &BTreeSet<String>,
+) -> Result<BTreeMap<String, String>> {
     handlebars.register_helper(
         "definitions",
         Box::new({
@@ -82,15 +215,16 @@ fn render_diagrams(
         }),
     );
 
-    Ok(model
+    model
         .diagrams
         .iter()
+        .filter(|(name, _)| names.contains(*name))
         .map(|(name, definition)| {
-            (
+            Ok((
                 name.clone(),
                 handlebars
                     .render_template(
-                        definition,
+                        &namespace::mangle_lines(definition, &model.nodes),
                         &iter::once(("diagram-name".to_string(), name.clone()))
                             .chain(model.nodes.iter().filter_map(|(id, node)| {
                                 node.name
@@ -99,47 +233,303 @@ fn render_diagrams(
                             }))
                             .collect::<BTreeMap<_, _>>(),
                     )
-                    .unwrap(),
-            )
+                    .map_err(|source| Error::DiagramRender {
+                        diagram: name.clone(),
+                        source: Box::new(source),
+                    })?,
+            ))
         })
-        .collect())
+        .collect()
 }
 
-fn process<T>(
+fn render(
     handlebars: &Handlebars,
-    data: &T,
+    diagrams: &BTreeMap<String, String>,
+    pages: &[PageInfo],
+    templates: &[PathBuf],
+    page: &Page,
+) -> Result<String> {
+    let mut data = page.data(diagrams, pages)?;
+    let content = handlebars.render_template(&page.body, &data)?;
+    let layout = match &page.front.layout {
+        Some(layout) => layout,
+        None => return Ok(content),
+    };
+    let extension = page.path.extension().unwrap_or_default().to_string_lossy();
+    let layout = templates
+        .iter()
+        .rev()
+        .map(|template| template.join(LAYOUTS).join(layout))
+        .find_map(|path| {
+            fs::read(path.with_extension(&*extension))
+                .or_else(|_| fs::read(path.with_extension(format!("{extension}.{TEMPLATE}"))))
+                .ok()
+        })
+        .ok_or_else(|| Error::PageHasUnknownLayout {
+            path: page.src.clone(),
+            layout: layout.clone(),
+        })?;
+    data.as_object_mut()
+        .unwrap()
+        .insert("body".to_string(), Value::String(content));
+    Ok(handlebars.render_template(&String::from_utf8_lossy(&layout), &data)?)
+}
+
+#[derive(Default)]
+pub(crate) struct State {
+    model: Option<Model>,
+    model_files: BTreeSet<PathBuf>,
+    resolved: Arc<Model>,
+    diagrams: BTreeMap<String, String>,
+    pages: BTreeMap<PathBuf, Page>,
+    infos: Value,
+}
+
+impl State {
+    fn changed_diagrams(
+        &mut self,
+        model: Model,
+        options: &Options,
+        full: bool,
+    ) -> Result<BTreeSet<String>> {
+        let old = self.model.take().unwrap_or_default();
+        let nodes: BTreeSet<_> = old
+            .nodes
+            .keys()
+            .chain(model.nodes.keys())
+            .filter(|id| old.nodes.get(*id) != model.nodes.get(*id))
+            .collect();
+        let changed: BTreeSet<_> = model
+            .diagrams
+            .iter()
+            .filter(|(name, source)| {
+                full || old.diagrams.get(*name) != Some(source)
+                    || nodes.iter().any(|id| source.contains(id.as_str()))
+            })
+            .map(|(name, _)| name.clone())
+            .chain(
+                old.diagrams
+                    .keys()
+                    .filter(|name| !model.diagrams.contains_key(*name))
+                    .cloned(),
+            )
+            .collect();
+        self.diagrams
+            .retain(|name, _| model.diagrams.contains_key(name));
+        let resolved = Arc::new(resolve(model.clone(), options)?);
+        self.diagrams.append(&mut render_diagrams(
+            new_handlebars(options),
+            Arc::clone(&resolved),
+            &changed,
+        )?);
+        self.model = Some(model);
+        self.resolved = resolved;
+        Ok(changed)
+    }
+
+    pub(crate) fn resolved(&self) -> &Arc<Model> {
+        &self.resolved
+    }
+
+    pub(crate) fn diagrams(&self) -> &BTreeMap<String, String> {
+        &self.diagrams
+    }
+
+    pub(crate) fn model_files(&self) -> &BTreeSet<PathBuf> {
+        &self.model_files
+    }
+
+    fn update(
+        &mut self,
+        models: &[PathBuf],
+        templates: &[PathBuf],
+        store: &mut Store,
+        options: &Options,
+        changed: Option<&[PathBuf]>,
+    ) -> Result<Summary> {
+        let mut summary = Summary::default();
+        let full = changed.is_none() || self.model.is_none();
+        let changed = changed.unwrap_or_default();
+        let diagrams = if full
+            || changed.iter().any(|path| {
+                self.model_files.contains(path)
+                    || models.iter().any(|model| path.starts_with(model))
+            }) {
+            let (model, files) = Model::load(models, &options.packages)?;
+            self.model_files = files;
+            self.changed_diagrams(model, options, full)?
+        } else {
+            BTreeSet::new()
+        };
+        let filter = Filter::new(&options.include, &options.exclude)?;
+        let output = store.dir().map(Path::to_path_buf);
+        let files: BTreeMap<_, _> = templates
+            .iter()
+            .flat_map(|template| {
+                let layouts = template.join(LAYOUTS);
+                let output = output
+                    .clone()
+                    .filter(|output| !template.starts_with(output));
+                WalkDir::new(template)
+                    .sort_by_file_name()
+                    .into_iter()
+                    .filter_map(|item| item.ok())
+                    .filter(|item| item.file_type().is_file())
+                    .filter(move |item| {
+                        output
+                            .as_ref()
+                            .map(|output| !item.path().starts_with(output))
+                            .unwrap_or(true)
+                    })
+                    .filter(move |item| !item.path().starts_with(&layouts))
+                    .map(move |item| {
+                        (
+                            item.path().strip_prefix(template).unwrap().to_path_buf(),
+                            item.into_path(),
+                        )
+                    })
+            })
+            .collect();
+        let (sources, assets): (BTreeMap<_, _>, BTreeMap<_, _>) = files
+            .into_iter()
+            .partition(|(path, _)| is_template(path, options));
+        self.pages.retain(|path, _| sources.contains_key(path));
+        let mut dirty: BTreeSet<_> = sources
+            .iter()
+            .filter(|(path, src)| {
+                full || changed.contains(src)
+                    || self
+                        .pages
+                        .get(*path)
+                        .map(|page| page.src != **src)
+                        .unwrap_or(true)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        dirty.iter().try_for_each(|path| -> Result<()> {
+            self.pages
+                .insert(path.clone(), Page::new(&sources[path], &output_path(path))?);
+            Ok(())
+        })?;
+        let layouts_changed = changed.iter().any(|path| {
+            templates
+                .iter()
+                .any(|template| path.starts_with(template.join(LAYOUTS)))
+        });
+        let pages: Vec<_> = self
+            .pages
+            .iter()
+            .filter(|(_, page)| !page.front.draft)
+            .collect();
+        let infos: Vec<_> = pages.iter().map(|(_, page)| page.info()).collect();
+        let infos_value = serde_json::to_value(&infos)?;
+        let infos_changed = infos_value != self.infos;
+        dirty.extend(
+            pages
+                .iter()
+                .filter(|(_, page)| {
+                    infos_changed
+                        || layouts_changed && page.front.layout.is_some()
+                        || diagrams.iter().any(|name| page.uses(name))
+                })
+                .map(|(path, _)| (*path).clone()),
+        );
+        let mmd = export_dir(options.export.as_deref())?;
+        let svg = export_dir(options.svg.as_deref())?;
+        let exported = self
+            .diagrams
+            .iter()
+            .flat_map(|(name, diagram)| {
+                mmd.map(|dir| Ok((export_path(dir, name, "mmd")?, name, None)))
+                    .into_iter()
+                    .chain(svg.and_then(|dir| {
+                        Flowchart::parse(markdown::strip_fence(diagram))
+                            .map(|chart| Ok((export_path(dir, name, "svg")?, name, Some(chart))))
+                    }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let links: BTreeMap<_, _> = exported
+            .iter()
+            .filter(|(_, _, chart)| chart.is_some() && options.svg_links)
+            .map(|(path, name, _)| (*name, path))
+            .collect();
+        let handlebars = new_handlebars(options);
+        pages.iter().try_for_each(|(path, page)| {
+            if dirty.contains(*path) {
+                let content = if links.is_empty() {
+                    render(&handlebars, &self.diagrams, &infos, templates, page)?
+                } else {
+                    let diagrams = linked(&self.diagrams, &links, &page.path);
+                    render(&handlebars, &diagrams, &infos, templates, page)?
+                };
+                let content = with_banner(&page.path, content, options);
+                store.write(&page.path, content.as_bytes(), &mut summary)
+            } else {
+                summary.unchanged.push(page.path.clone());
+                Ok(())
+            }
+        })?;
+        let assets: Vec<_> = assets
+            .iter()
+            .filter(|(path, _)| filter.matches(path))
+            .collect();
+        assets
+            .iter()
+            .try_for_each(|(path, src)| store.copy(src, path, &mut summary))?;
+        exported.iter().try_for_each(|(path, name, chart)| {
+            if full || diagrams.contains(*name) {
+                let content = match chart {
+                    Some(chart) => chart.to_svg(),
+                    None => format!("{}\n", markdown::strip_fence(&self.diagrams[*name])),
+                };
+                let content = with_banner(path, content, options);
+                store.write(path, content.as_bytes(), &mut summary)
+            } else {
+                summary.unchanged.push(path.clone());
+                Ok(())
+            }
+        })?;
+        store.finish(
+            &pages
+                .iter()
+                .map(|(_, page)| page.path.clone())
+                .chain(assets.iter().map(|(path, _)| (*path).clone()))
+                .chain(exported.iter().map(|(path, _, _)| path.clone()))
+                .collect(),
+            &mut summary,
+        )?;
+        self.infos = infos_value;
+        Ok(summary)
+    }
+
+    pub(crate) fn build(
+        &mut self,
+        models: &[PathBuf],
+        templates: &[PathBuf],
+        store: &mut Store,
+        options: &Options,
+        changed: Option<&[PathBuf]>,
+    ) -> Result<Summary> {
+        let result = self.update(models, templates, store, options, changed);
+        match &result {
+            Ok(summary) => println!("{summary}"),
+            Err(_) => *self = Self::default(),
+        }
+        result
+    }
+}
+
+pub(crate) fn build(
+    models: &[PathBuf],
+    templates: &[PathBuf],
     output: &Path,
-    template: &Path,
-    src: &Path,
-) -> Result<()>
-where
-    T: Serialize,
-{
-    let dst = output.join(src.strip_prefix(template).unwrap());
-    println!(
-        "rendering {}...",
-        dst.strip_prefix(output).unwrap().to_str().unwrap()
-    );
-    let mut dst = File::create(dst).unwrap();
-    dst.write_all(b"<!-- DO NOT EDIT; Autogenerated -->\n\n")
-        .unwrap();
-    handlebars.render_template_to_write(
-        std::str::from_utf8(&fs::read(src).unwrap()).unwrap(),
-        data,
-        dst,
-    )?;
-    Ok(())
-}
-
-pub(crate) fn build(model: &Path, template: &Path, output: &Path) -> Result<()> {
-    let data = render_diagrams(new_handlebars(), Model::new(model)?)?;
-    let handlebars = new_handlebars();
-    WalkDir::new(template)
-        .into_iter()
-        .filter_map(|item| item.ok())
-        .filter(|item| item.file_type().is_file())
-        .filter(|item| item.path().extension().unwrap_or(&OsString::new()) == "md")
-        .filter(|item| !item.path().ancestors().any(|path| path == output))
-        .try_for_each(|item| process(&handlebars, &data, output, template, item.path()))?;
-    Ok(())
+    options: &Options,
+) -> Result<Summary> {
+    State::default().build(
+        models,
+        templates,
+        &mut Store::Dir(output.to_path_buf()),
+        options,
+        None,
+    )
 }
@@ -0,0 +1,143 @@
+use {
+    crate::{Error, Result},
+    serde::{Deserialize, Serialize},
+    serde_json::{Map, Value},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+const DELIMITER: &str = "---";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FrontMatter {
+    pub title: Option<String>,
+    pub layout: Option<String>,
+    pub output: Option<PathBuf>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub draft: bool,
+    pub diagrams: Option<BTreeSet<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Page {
+    pub src: PathBuf,
+    pub path: PathBuf,
+    pub front: FrontMatter,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PageInfo<'a> {
+    path: &'a Path,
+    title: &'a Option<String>,
+    layout: &'a Option<String>,
+    tags: &'a BTreeSet<String>,
+}
+
+fn split(content: &str) -> Option<(&str, &str)> {
+    let rest = content.strip_prefix(DELIMITER).and_then(|rest| {
+        rest.strip_prefix('\n')
+            .or_else(|| rest.strip_prefix("\r\n"))
+    })?;
+    let mut offset = 0;
+    rest.split_inclusive('\n').find_map(|line| {
+        let start = offset;
+        offset += line.len();
+        (line.trim_end() == DELIMITER).then(|| (&rest[..start], &rest[offset..]))
+    })
+}
+
+impl Page {
+    pub(crate) fn new(src: &Path, path: &Path) -> Result<Self> {
+        let content = String::from_utf8_lossy(&fs::read(src)?).into_owned();
+        let (front, body) = if let Some((front, body)) = split(&content) {
+            let front: FrontMatter = if front.trim().is_empty() {
+                FrontMatter::default()
+            } else {
+                serde_yaml::from_str(front).map_err(|source| Error::PageFrontMatter {
+                    path: src.to_path_buf(),
+                    source,
+                })?
+            };
+            (front, body.to_string())
+        } else {
+            (FrontMatter::default(), content.clone())
+        };
+        let mut path = path.to_path_buf();
+        if let Some(output) = &front.output {
+            if !output
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(Error::PageHasInvalidOutput {
+                    path: src.to_path_buf(),
+                    output: output.clone(),
+                });
+            }
+            path.set_file_name(output);
+        }
+        Ok(Self {
+            src: src.to_path_buf(),
+            path,
+            front,
+            body,
+        })
+    }
+
+    pub(crate) fn info(&self) -> PageInfo<'_> {
+        PageInfo {
+            path: &self.path,
+            title: &self.front.title,
+            layout: &self.front.layout,
+            tags: &self.front.tags,
+        }
+    }
+
+    pub(crate) fn uses(&self, diagram: &str) -> bool {
+        self.front
+            .diagrams
+            .as_ref()
+            .map(|selected| selected.contains(diagram))
+            .unwrap_or_else(|| self.front.layout.is_some() || self.body.contains(diagram))
+    }
+
+    pub(crate) fn data(
+        &self,
+        diagrams: &BTreeMap<String, String>,
+        pages: &[PageInfo],
+    ) -> Result<Value> {
+        if let Some(selected) = &self.front.diagrams {
+            let unknown: Vec<_> = selected
+                .iter()
+                .filter(|name| !diagrams.contains_key(*name))
+                .cloned()
+                .collect();
+            if !unknown.is_empty() {
+                return Err(Error::PageHasUnknownDiagram {
+                    path: self.src.clone(),
+                    list: unknown,
+                });
+            }
+        }
+        let mut data: Map<_, _> = diagrams
+            .iter()
+            .filter(|(name, _)| {
+                self.front
+                    .diagrams
+                    .as_ref()
+                    .map(|selected| selected.contains(*name))
+                    .unwrap_or(true)
+            })
+            .map(|(name, diagram)| (name.clone(), Value::String(diagram.clone())))
+            .collect();
+        data.insert("page".to_string(), serde_json::to_value(self.info())?);
+        data.insert("pages".to_string(), serde_json::to_value(pages)?);
+        Ok(Value::Object(data))
+    }
+}
@@ -1,11 +1,11 @@
 use {
-    crate::{output::Merge, Error, Result},
+    crate::{namespace, output::Merge, Error, Result},
     handlebars::Handlebars,
     serde::{Deserialize, Serialize},
     std::collections::HashSet,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Relation {
     pub parent: Option<String>,
     pub tags: Option<HashSet<String>>,
@@ -44,12 +44,17 @@ impl Merge for Relation {
 
 impl Relation {
     pub(crate) fn render_definition(&mut self, handlebars: &Handlebars) -> Result<()> {
+        let context = Self {
+            left: self.left.as_deref().map(namespace::mangle),
+            right: self.right.as_deref().map(namespace::mangle),
+            ..self.clone()
+        };
         self.definition = Some(
             handlebars.render_template(
                 self.definition
                     .as_ref()
-                    .ok_or_else(|| Error::RelationHasNoDefinition(self.clone()))?,
-                self,
+                    .ok_or_else(|| Error::RelationHasNoDefinition(Box::new(self.clone())))?,
+                &context,
             )?,
         );
         Ok(())
@@ -1,112 +1,552 @@
 use {
-    crate::Result,
+    crate::{
+        api::{self, Api},
+        markdown,
+        store::{self, Files, Shared},
+        Error, Result,
+    },
     axum::{
-        http::{Request, Response, StatusCode},
-        response::IntoResponse,
-        routing, Router,
+        body::{self, Body, Bytes, Full},
+        http::{header, HeaderMap, HeaderValue, Request, StatusCode},
+        middleware::{self, Next},
+        response::{
+            sse::{Event, KeepAlive, Sse},
+            IntoResponse, Response,
+        },
+        routing, Extension, Router,
     },
+    futures::{future, stream, Stream, StreamExt},
+    httpdate::HttpDate,
+    percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS},
     std::{
+        collections::{hash_map::DefaultHasher, BTreeMap},
+        convert::Infallible,
         fs,
-        future::{self, Ready},
+        hash::{Hash, Hasher},
         iter,
         net::SocketAddr,
-        path::{Path, PathBuf},
-        str::FromStr,
-        task::{Context, Poll},
+        ops::Range,
+        path::{Component, Path, PathBuf},
+        sync::{Arc, RwLock},
+        time::SystemTime,
+    },
+    tokio::{
+        signal,
+        sync::{
+            broadcast::{self, error::RecvError},
+            watch,
+        },
     },
-    tower::Service,
-    tower_http::services::ServeDir,
 };
 
-async fn handle_error<E>(_: E) -> impl IntoResponse {
-    (StatusCode::INTERNAL_SERVER_ERROR, "Something went wrong...")
-}
+const EVENTS: &str = "/_symo/events";
+
+const RELOAD_SCRIPT: &str = r#"<script>
+new EventSource("/_symo/events").addEventListener("reload", (event) => {
+  const paths = JSON.parse(event.data);
+  const path = decodeURIComponent(location.pathname);
+  if (paths.length == 0 || path.endsWith("/") || paths.includes(path)) {
+    location.reload();
+  }
+});
+</script>
+"#;
+
+const ERROR_OVERLAY: &str = r#"<div id="symo-error" style="position: fixed; inset: 0; overflow: auto; z-index: 9999; padding: 2em; background: rgba(0, 0, 0, 0.9); color: #ff8080">
+<h2>symo build failed</h2>
+"#;
 
 #[derive(Clone)]
-struct DirListingService {
-    base: PathBuf,
+pub(crate) struct Live {
+    reload: broadcast::Sender<Vec<String>>,
+    error: Arc<RwLock<Option<String>>>,
+    stopped: Arc<watch::Sender<bool>>,
 }
 
-impl DirListingService {
-    fn new(base: &Path) -> Self {
+impl Live {
+    pub(crate) fn new() -> Self {
         Self {
-            base: base.canonicalize().unwrap(),
+            reload: broadcast::channel(16).0,
+            error: Arc::new(RwLock::new(None)),
+            stopped: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    pub(crate) fn succeeded(&self, paths: Vec<String>) {
+        let failed = self.error.write().unwrap().take().is_some();
+        if failed {
+            self.reload.send(vec![]).ok();
+        } else if !paths.is_empty() {
+            self.reload.send(paths).ok();
+        }
+    }
+
+    pub(crate) fn failed(&self, error: &Error) {
+        *self.error.write().unwrap() = Some(error.to_string());
+        self.reload.send(vec![]).ok();
+    }
+
+    fn stop(&self) {
+        self.stopped.send_replace(true);
+    }
+}
+
+async fn events(
+    Extension(live): Extension<Live>,
+) -> Sse<impl Stream<Item = std::result::Result<Event, Infallible>>> {
+    Sse::new(
+        stream::unfold(live.reload.subscribe(), |mut rx| async move {
+            let paths = match rx.recv().await {
+                Ok(paths) => paths,
+                Err(RecvError::Lagged(_)) => vec![],
+                Err(RecvError::Closed) => return None,
+            };
+            Some((
+                Ok(Event::default().event("reload").json_data(paths).unwrap()),
+                rx,
+            ))
+        })
+        .take_until({
+            let mut stopped = live.stopped.subscribe();
+            async move {
+                while !*stopped.borrow() {
+                    if stopped.changed().await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }),
+    )
+    .keep_alive(KeepAlive::default())
+}
+
+fn inject_script(content: &[u8], error: Option<&str>) -> Vec<u8> {
+    let content = String::from_utf8_lossy(content);
+    let script = error
+        .map(|error| {
+            format!(
+                "{}<pre>{}</pre>\n</div>\n",
+                ERROR_OVERLAY,
+                markdown::escape_html(error)
+            )
+        })
+        .unwrap_or_default()
+        + RELOAD_SCRIPT;
+    if let Some(pos) = content.rfind("</body>") {
+        format!("{}{}{}", &content[..pos], script, &content[pos..])
+    } else {
+        format!("{content}{script}")
+    }
+    .into_bytes()
+}
+
+async fn inject(request: Request<Body>, next: Next<Body>) -> impl IntoResponse {
+    let live = request.extensions().get::<Live>().unwrap().clone();
+    let response = next.run(request).await;
+    if !response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("text/html"))
+        .unwrap_or(false)
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let content = match hyper::body::to_bytes(body).await {
+        Ok(content) => content,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let error = live.error.read().unwrap().clone();
+    Response::from_parts(
+        parts,
+        body::boxed(Full::new(Bytes::from(inject_script(
+            &content,
+            error.as_deref(),
+        )))),
+    )
+}
+
+async fn mermaid() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/javascript")],
+        include_bytes!("../assets/mermaid.min.js").as_slice(),
+    )
+}
+
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'\'')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/');
+
+const LANDING: [&str; 2] = ["README.md", "index.md"];
+
+const HIDDEN: [&str; 2] = [".tmp", store::TMP];
+
+struct Entry {
+    name: String,
+    dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+enum Found {
+    File(PathBuf, Vec<u8>, Option<SystemTime>),
+    Dir(Vec<Entry>, Option<Vec<u8>>),
+}
+
+#[derive(Clone)]
+pub(crate) enum Source {
+    Dir(PathBuf),
+    Memory(Shared),
+}
+
+fn listing(mut entries: Vec<Entry>, landing: Option<Vec<u8>>, uri: &str) -> String {
+    entries.retain(|entry| {
+        !entry.name.starts_with('.') && !HIDDEN.iter().any(|suffix| entry.name.ends_with(suffix))
+    });
+    entries.sort_by(|a, b| b.dir.cmp(&a.dir).then_with(|| a.name.cmp(&b.name)));
+    let title = format!("Index of {}", percent_decode_str(uri).decode_utf8_lossy());
+    let landing = landing
+        .map(|content| markdown::to_html(&String::from_utf8_lossy(&content)))
+        .unwrap_or_default();
+    markdown::page(
+        &title,
+        &iter::once(format!(
+            "<h1>{}</h1>\n<table>\n<tr><th>Name</th><th>Size</th><th>Modified</th></tr>\n",
+            markdown::escape_html(&title)
+        ))
+        .chain(
+            iter::once("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n".to_string())
+                .filter(|_| uri != "/"),
+        )
+        .chain(entries.iter().map(|entry| {
+            let suffix = if entry.dir { "/" } else { "" };
+            format!(
+                "<tr><td><a href=\"{}{suffix}\">{}{suffix}</a></td><td>{}</td><td>{}</td></tr>\n",
+                utf8_percent_encode(&entry.name, SEGMENT),
+                markdown::escape_html(&entry.name),
+                if entry.dir {
+                    String::new()
+                } else {
+                    entry.size.to_string()
+                },
+                entry
+                    .modified
+                    .map(httpdate::fmt_http_date)
+                    .unwrap_or_default(),
+            )
+        }))
+        .chain(iter::once("</table>\n".to_string()))
+        .chain(iter::once(landing))
+        .collect::<String>(),
+    )
+}
+
+fn relative(uri: &str) -> std::result::Result<PathBuf, StatusCode> {
+    percent_decode_str(uri)
+        .decode_utf8()
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .try_fold(PathBuf::new(), |path, segment| {
+            let mut components = Path::new(segment).components();
+            match (components.next(), components.next()) {
+                (Some(Component::Normal(_)), None) if !segment.contains(['\\', '\0']) => {
+                    Ok(path.join(segment))
+                }
+                _ => Err(StatusCode::BAD_REQUEST),
+            }
+        })
+}
+
+fn find_dir(base: &Path, relative: &Path) -> std::result::Result<Found, StatusCode> {
+    let path = base
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    if !path.starts_with(base) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if !path.is_dir() {
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        return fs::read(&path)
+            .map(|content| Found::File(path, content, modified))
+            .map_err(|_| StatusCode::NOT_FOUND);
+    }
+    let entries = fs::read_dir(&path)
+        .map_err(|_| StatusCode::FORBIDDEN)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(Entry {
+                name: entry.file_name().to_str()?.to_string(),
+                dir: metadata.is_dir(),
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            })
+        })
+        .collect();
+    let landing = LANDING
+        .iter()
+        .map(|name| path.join(name))
+        .find(|path| path.is_file())
+        .and_then(|path| fs::read(path).ok());
+    Ok(Found::Dir(entries, landing))
+}
+
+fn find_memory(files: &Files, relative: &Path) -> std::result::Result<Found, StatusCode> {
+    if let Some(content) = files.get(relative) {
+        return Ok(Found::File(relative.to_path_buf(), content.clone(), None));
+    }
+    let entries: BTreeMap<_, _> = files
+        .iter()
+        .filter_map(|(path, content)| Some((path.strip_prefix(relative).ok()?, content)))
+        .filter_map(|(rest, content)| {
+            let mut components = rest.components();
+            let name = components.next()?.as_os_str().to_str()?.to_string();
+            let dir = components.next().is_some();
+            Some((
+                name.clone(),
+                Entry {
+                    name,
+                    dir,
+                    size: if dir { 0 } else { content.len() as u64 },
+                    modified: None,
+                },
+            ))
+        })
+        .collect();
+    if entries.is_empty() && !relative.as_os_str().is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let landing = LANDING
+        .iter()
+        .find_map(|name| files.get(&relative.join(name)).cloned());
+    Ok(Found::Dir(entries.into_values().collect(), landing))
+}
+
+impl Source {
+    fn find(&self, uri: &str) -> std::result::Result<Found, StatusCode> {
+        let relative = relative(uri)?;
+        match self {
+            Self::Dir(base) => find_dir(base, &relative),
+            Self::Memory(shared) => {
+                let files = Arc::clone(&shared.read().unwrap());
+                find_memory(&files, &relative)
+            }
         }
     }
 }
 
-impl<ReqBody> Service<Request<ReqBody>> for DirListingService
-where
-    ReqBody: Send + 'static,
-{
-    type Response = Response<String>;
-    type Error = std::io::Error;
-    type Future = Ready<std::result::Result<Self::Response, Self::Error>>;
-
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
-        Poll::Ready(Ok(()))
-    }
-
-    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
-        future::ready(
-            if let Some(path) = &self
-                .base
-                .join(PathBuf::from_str(&request.uri().path()[1..]).unwrap())
-                .canonicalize()
-                .ok()
-                .iter()
-                .filter(|path| (path).is_dir())
-                .find(|path| path.ancestors().any(|path| (*path) == self.base))
-            {
-                Ok(Response::builder()
-                    .status(StatusCode::OK)
-                    .body(
-                        iter::once(format!(
-                            "<html>\n<meta>\n<title>Directory {}</title>\n</meta>\n<body>\n<ul>",
-                            request.uri().path()
-                        ))
-                        .chain(
-                            iter::once("<li><a href=\"..\">..</a></li>\n".to_string())
-                                .filter(|_| request.uri().path() != "/"),
-                        )
-                        .chain(
-                            fs::read_dir(path)
-                                .unwrap()
-                                .filter_map(|path| path.ok())
-                                .filter_map(|path| {
-                                    path.file_name().to_str().map(|name| name.to_string())
-                                })
-                                .map(|name| format!("<li><a href=\"{name}\">{name}</a></li>\n")),
-                        )
-                        .chain(iter::once("</ul>\n</body>\n</html>\n".to_string()))
-                        .collect(),
-                    )
-                    .unwrap())
-            } else {
-                Ok(Response::builder()
-                    .status(StatusCode::NOT_FOUND)
-                    .body(String::new())
-                    .unwrap())
-            },
+fn html(content: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+        content,
+    )
+        .into_response()
+}
+
+fn etag(content: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn http_date(headers: &HeaderMap, name: header::HeaderName) -> Option<HttpDate> {
+    header_str(headers, name)?.parse().ok()
+}
+
+fn not_modified(headers: &HeaderMap, etag: &str, modified: Option<SystemTime>) -> bool {
+    match header_str(headers, header::IF_NONE_MATCH) {
+        Some(tags) => tags
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag),
+        None => http_date(headers, header::IF_MODIFIED_SINCE)
+            .zip(modified)
+            .map(|(since, modified)| HttpDate::from(modified) <= since)
+            .unwrap_or(false),
+    }
+}
+
+fn range(
+    headers: &HeaderMap,
+    etag: &str,
+    modified: Option<SystemTime>,
+    len: usize,
+) -> Option<std::result::Result<Range<usize>, ()>> {
+    if let Some(validator) = header_str(headers, header::IF_RANGE) {
+        let date = http_date(headers, header::IF_RANGE).zip(modified);
+        if validator != etag
+            && !matches!(date, Some((date, modified)) if HttpDate::from(modified) == date)
+        {
+            return None;
+        }
+    }
+    let (start, end) = header_str(headers, header::RANGE)?
+        .strip_prefix("bytes=")
+        .filter(|ranges| !ranges.contains(','))?
+        .trim()
+        .split_once('-')?;
+    let range = match (start, end) {
+        ("", suffix) => len.saturating_sub(suffix.parse::<usize>().ok()?)..len,
+        (start, "") => start.parse().ok()?..len,
+        (start, end) => start.parse().ok()?..end.parse::<usize>().ok()?.saturating_add(1).min(len),
+    };
+    Some(if range.start < range.end {
+        Ok(range)
+    } else {
+        Err(())
+    })
+}
+
+fn file(
+    headers: &HeaderMap,
+    path: &Path,
+    content: Vec<u8>,
+    modified: Option<SystemTime>,
+) -> Response {
+    let mime = mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string();
+    if mime.starts_with("text/html") {
+        return ([(header::CONTENT_TYPE, mime)], content).into_response();
+    }
+    let etag = etag(&content);
+    let len = content.len();
+    let (status, content_range, content) = if not_modified(headers, &etag, modified) {
+        (StatusCode::NOT_MODIFIED, None, vec![])
+    } else {
+        match range(headers, &etag, modified, len) {
+            Some(Ok(range)) => (
+                StatusCode::PARTIAL_CONTENT,
+                Some(format!("bytes {}-{}/{len}", range.start, range.end - 1)),
+                content[range].to_vec(),
+            ),
+            Some(Err(())) => (
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                Some(format!("bytes */{len}")),
+                vec![],
+            ),
+            None => (StatusCode::OK, None, content),
+        }
+    };
+    let mut response = (status, content).into_response();
+    [
+        Some((header::CONTENT_TYPE, mime)),
+        Some((header::ETAG, etag)),
+        Some((header::ACCEPT_RANGES, "bytes".to_string())),
+        modified.map(|modified| (header::LAST_MODIFIED, httpdate::fmt_http_date(modified))),
+        content_range.map(|content_range| (header::CONTENT_RANGE, content_range)),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|(name, value)| Some((name, HeaderValue::from_str(&value).ok()?)))
+    .for_each(|(name, value)| {
+        response.headers_mut().insert(name, value);
+    });
+    response
+}
+
+async fn files(source: Source, request: Request<Body>) -> Response {
+    let uri = request.uri();
+    let found = tokio::task::spawn_blocking({
+        let path = uri.path().to_string();
+        move || source.find(&path)
+    })
+    .await
+    .unwrap_or(Err(StatusCode::INTERNAL_SERVER_ERROR));
+    match found {
+        Err(status) => status.into_response(),
+        Ok(Found::Dir(entries, landing)) if uri.path().ends_with('/') => {
+            html(listing(entries, landing, uri.path()))
+        }
+        Ok(Found::Dir(..)) => (
+            StatusCode::MOVED_PERMANENTLY,
+            [(
+                header::LOCATION,
+                iter::once(uri.path())
+                    .chain(iter::once("/"))
+                    .chain(uri.query().map(|_| "?"))
+                    .chain(uri.query())
+                    .collect::<String>(),
+            )],
         )
+            .into_response(),
+        Ok(Found::File(path, content, _))
+            if uri.query() != Some("raw")
+                && path.extension().map(|ext| ext == "md").unwrap_or(false) =>
+        {
+            html(markdown::page(
+                &path.file_name().unwrap().to_string_lossy(),
+                &markdown::to_html(&String::from_utf8_lossy(&content)),
+            ))
+        }
+        Ok(Found::File(path, content, modified)) => {
+            file(request.headers(), &path, content, modified)
+        }
     }
 }
 
-pub(crate) async fn serve(path: &Path, addr: &SocketAddr) -> Result<()> {
+pub(crate) async fn shutdown() {
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+    tokio::select! {
+        _ = signal::ctrl_c() => {}
+        _ = terminate => {}
+    }
+}
+
+pub(crate) async fn serve(source: Source, addr: &SocketAddr, live: Live, api: Api) -> Result<()> {
+    let source = match source {
+        Source::Dir(path) => Source::Dir(path.canonicalize()?),
+        source => source,
+    };
     let server = axum::Server::bind(addr).serve(
         Router::new()
-            .fallback(
-                routing::get_service(
-                    ServeDir::new(path)
-                        .append_index_html_on_directories(false)
-                        .fallback(DirListingService::new(path)),
-                )
-                .handle_error(handle_error),
-            )
+            .route(EVENTS, routing::get(events))
+            .route(markdown::MERMAID, routing::get(mermaid))
+            .merge(api::router(api))
+            .fallback(routing::get(move |request| files(source.clone(), request)))
+            .layer(middleware::from_fn(inject))
+            .layer(Extension(live.clone()))
             .into_make_service(),
     );
     println!("listening on http://{}", server.local_addr());
 
-    server.await?;
+    server
+        .with_graceful_shutdown(async move {
+            shutdown().await;
+            println!("shutting down");
+            live.stop();
+        })
+        .await?;
     Ok(())
 }
@@ -0,0 +1,6 @@
+pub(crate) const PACKAGE: &str = "symo";
+
+pub(crate) const LIBRARIES: [(&str, &str); 2] = [
+    ("c4", include_str!("../stdlib/c4.yaml")),
+    ("dataflow", include_str!("../stdlib/dataflow.yaml")),
+];
@@ -0,0 +1,119 @@
+use {
+    crate::{manifest, summary::Summary, Result},
+    filetime::FileTime,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs, io,
+        path::{Path, PathBuf},
+        sync::{Arc, RwLock},
+    },
+};
+
+pub(crate) const TMP: &str = ".symo-tmp";
+
+pub(crate) type Files = BTreeMap<PathBuf, Vec<u8>>;
+
+pub(crate) type Shared = Arc<RwLock<Arc<Files>>>;
+
+pub(crate) enum Store {
+    Dir(PathBuf),
+    Memory { files: Files, shared: Shared },
+}
+
+fn replace(dst: &Path, write: impl FnOnce(&Path) -> io::Result<()>) -> Result<()> {
+    fs::create_dir_all(dst.parent().unwrap())?;
+    let tmp = dst.with_file_name(format!(
+        ".{}{TMP}",
+        dst.file_name().unwrap().to_string_lossy()
+    ));
+    if let Err(err) = write(&tmp).and_then(|_| fs::rename(&tmp, dst)) {
+        fs::remove_file(&tmp).ok();
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+impl Store {
+    pub(crate) fn memory(shared: Shared) -> Self {
+        let files = shared.read().unwrap().as_ref().clone();
+        Self::Memory { files, shared }
+    }
+
+    pub(crate) fn dir(&self) -> Option<&Path> {
+        match self {
+            Self::Dir(output) => Some(output),
+            Self::Memory { .. } => None,
+        }
+    }
+
+    pub(crate) fn write(
+        &mut self,
+        path: &Path,
+        content: &[u8],
+        summary: &mut Summary,
+    ) -> Result<()> {
+        let current = match self {
+            Self::Dir(output) => fs::read(output.join(path)).ok(),
+            Self::Memory { files, .. } => files.get(path).cloned(),
+        };
+        if current.as_deref() == Some(content) {
+            summary.unchanged.push(path.to_path_buf());
+            return Ok(());
+        }
+        match self {
+            Self::Dir(output) => replace(&output.join(path), |tmp| fs::write(tmp, content))?,
+            Self::Memory { files, .. } => {
+                files.insert(path.to_path_buf(), content.to_vec());
+            }
+        }
+        summary.add(path, current.is_some());
+        Ok(())
+    }
+
+    pub(crate) fn copy(&mut self, src: &Path, path: &Path, summary: &mut Summary) -> Result<()> {
+        let output = match self {
+            Self::Dir(output) => output,
+            Self::Memory { .. } => return self.write(path, &fs::read(src)?, summary),
+        };
+        let dst = output.join(path);
+        let metadata = fs::metadata(src)?;
+        let mtime = FileTime::from_last_modification_time(&metadata);
+        let current = fs::metadata(&dst);
+        if let Ok(current) = &current {
+            if current.len() == metadata.len()
+                && FileTime::from_last_modification_time(current) == mtime
+            {
+                summary.unchanged.push(path.to_path_buf());
+                return Ok(());
+            }
+        }
+        replace(&dst, |tmp| {
+            fs::copy(src, tmp)?;
+            filetime::set_file_mtime(tmp, mtime)
+        })?;
+        summary.add(path, current.is_ok());
+        Ok(())
+    }
+
+    pub(crate) fn finish(
+        &mut self,
+        generated: &BTreeSet<PathBuf>,
+        summary: &mut Summary,
+    ) -> Result<()> {
+        match self {
+            Self::Dir(output) => manifest::update(output, generated, summary),
+            Self::Memory { files, shared } => {
+                files.retain(|path, _| {
+                    if generated.contains(path) {
+                        true
+                    } else {
+                        summary.removed.push(path.clone());
+                        false
+                    }
+                });
+                *shared.write().unwrap() = Arc::new(files.clone());
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub created: Vec<PathBuf>,
+    pub updated: Vec<PathBuf>,
+    pub unchanged: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "created {}, updated {}, unchanged {}, removed {}",
+            self.created.len(),
+            self.updated.len(),
+            self.unchanged.len(),
+            self.removed.len()
+        )
+    }
+}
+
+impl Summary {
+    pub fn changed(&self) -> impl Iterator<Item = &PathBuf> {
+        self.created
+            .iter()
+            .chain(self.updated.iter())
+            .chain(self.removed.iter())
+    }
+
+    pub(crate) fn add(&mut self, path: &Path, existed: bool) {
+        if existed {
+            self.updated.push(path.to_path_buf());
+        } else {
+            self.created.push(path.to_path_buf());
+        }
+    }
+}
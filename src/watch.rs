@@ -1,50 +1,236 @@
 use {
-    crate::Result,
-    notify::RecursiveMode,
-    std::{fs, path::Path, sync::mpsc, time::Duration},
+    crate::{Error, Options, Result},
+    notify::{Config, ErrorKind, PollWatcher, RecommendedWatcher, RecursiveMode},
+    notify_debouncer_mini::{DebounceEventResult, Debouncer},
+    std::{
+        collections::BTreeSet,
+        fs, io,
+        path::{Path, PathBuf},
+        sync::{mpsc, Arc, Mutex},
+        thread::{self, JoinHandle},
+        time::Duration,
+    },
 };
 
-pub(crate) fn watch(
-    model: &Path,
-    template: &Path,
-    output: &Path,
-    job: impl Fn() -> Result<()> + Send + 'static,
-) {
-    let model = fs::canonicalize(model).unwrap();
-    let template = fs::canonicalize(template).unwrap();
-    let output = fs::canonicalize(output).unwrap();
-    std::thread::spawn(move || {
-        let process = || {
-            if let Err(err) = job() {
-                eprintln!("error processing: {err}");
-            }
-        };
-
-        let (tx, rx) = mpsc::channel();
-
-        let mut debouncer =
-            notify_debouncer_mini::new_debouncer(Duration::from_secs(1), None, tx).unwrap();
-
-        debouncer
-            .watcher()
-            .watch(&model, RecursiveMode::Recursive)
-            .unwrap();
-        debouncer
-            .watcher()
-            .watch(&template, RecursiveMode::Recursive)
-            .unwrap();
-
-        process();
-
-        rx.iter().for_each(|e| {
-            if e.unwrap_or_default().iter().map(|e| &e.path).any(|path| {
-                !path
-                    .ancestors()
-                    .take_while(|path| *path != model && *path != template)
-                    .any(|path| *path == output)
-            }) {
-                process();
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+const POLL: Duration = Duration::from_secs(2);
+
+type Events = mpsc::Sender<Option<DebounceEventResult>>;
+
+fn unsupported(err: &notify::Error) -> bool {
+    match &err.kind {
+        ErrorKind::MaxFilesWatch => true,
+        ErrorKind::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::Unsupported | io::ErrorKind::StorageFull
+        ),
+        _ => false,
+    }
+}
+
+fn debouncer<T: notify::Watcher>(
+    paths: &[PathBuf],
+    debounce: Duration,
+    config: Config,
+    events: &Events,
+) -> Result<Debouncer<T>> {
+    let events = events.clone();
+    let mut debouncer = notify_debouncer_mini::new_debouncer_opt(
+        debounce,
+        None,
+        move |result| {
+            events.send(Some(result)).ok();
+        },
+        config,
+    )?;
+    paths
+        .iter()
+        .try_for_each(|path| debouncer.watcher().watch(path, RecursiveMode::Recursive))?;
+    Ok(debouncer)
+}
+
+enum Backend {
+    Native(Debouncer<RecommendedWatcher>),
+    Poll(Debouncer<PollWatcher>),
+}
+
+impl Backend {
+    fn poll(paths: &[PathBuf], options: &Options, events: &Events) -> Result<Self> {
+        Ok(Self::Poll(debouncer(
+            paths,
+            options.debounce.unwrap_or(DEBOUNCE),
+            Config::default().with_poll_interval(options.poll.unwrap_or(POLL)),
+            events,
+        )?))
+    }
+
+    fn start(paths: &[PathBuf], options: &Options, events: &Events) -> Result<Self> {
+        if options.poll.is_some() {
+            return Self::poll(paths, options, events);
+        }
+        match debouncer(
+            paths,
+            options.debounce.unwrap_or(DEBOUNCE),
+            Config::default(),
+            events,
+        ) {
+            Ok(debouncer) => Ok(Self::Native(debouncer)),
+            Err(Error::Notify { source }) if unsupported(&source) => {
+                eprintln!("watcher failed, falling back to polling: {source}");
+                Self::poll(paths, options, events)
             }
-        });
+            Err(err) => Err(err),
+        }
+    }
+
+    fn watch(&mut self, path: &Path) -> notify::Result<()> {
+        match self {
+            Self::Native(debouncer) => debouncer.watcher().watch(path, RecursiveMode::NonRecursive),
+            Self::Poll(debouncer) => debouncer.watcher().watch(path, RecursiveMode::NonRecursive),
+        }
+    }
+
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        match self {
+            Self::Native(debouncer) => debouncer.watcher().unwatch(path),
+            Self::Poll(debouncer) => debouncer.watcher().unwatch(path),
+        }
+    }
+
+    fn stop(self) {
+        match self {
+            Self::Native(debouncer) => debouncer.stop(),
+            Self::Poll(debouncer) => debouncer.stop(),
+        }
+    }
+}
+
+pub struct Watcher {
+    backend: Arc<Mutex<Option<Backend>>>,
+    events: Events,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        if let Some(backend) = self.backend.lock().unwrap().take() {
+            backend.stop();
+        }
+        self.events.send(None).ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn parents(files: &BTreeSet<PathBuf>) -> BTreeSet<PathBuf> {
+    files
+        .iter()
+        .filter_map(|file| file.parent())
+        .map(Path::to_path_buf)
+        .collect()
+}
+
+pub fn watch(
+    models: &[PathBuf],
+    templates: &[PathBuf],
+    output: Option<&Path>,
+    options: &Options,
+    mut job: impl FnMut(Option<&[PathBuf]>) -> Result<Vec<PathBuf>> + Send + 'static,
+) -> Result<Watcher> {
+    let roots = models
+        .iter()
+        .chain(templates)
+        .chain(options.packages.values())
+        .map(fs::canonicalize)
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let output = output.map(fs::canonicalize).transpose()?;
+
+    let (events, rx) = mpsc::channel();
+
+    let backend = Arc::new(Mutex::new(Some(Backend::start(&roots, options, &events)?)));
+
+    let thread = thread::spawn({
+        let backend = Arc::clone(&backend);
+        let events = events.clone();
+        let options = options.clone();
+        move || {
+            let mut outside = BTreeSet::new();
+            let mut process = |changed: Option<&[PathBuf]>, outside: &mut BTreeSet<PathBuf>| {
+                let files = match job(changed) {
+                    Ok(files) => files,
+                    Err(err) => {
+                        eprintln!("error processing: {err}");
+                        return;
+                    }
+                };
+                let files: BTreeSet<_> = files
+                    .into_iter()
+                    .filter(|file| !roots.iter().any(|root| file.starts_with(root)))
+                    .collect();
+                let (watched, dirs) = (parents(outside), parents(&files));
+                if let Some(backend) = backend.lock().unwrap().as_mut() {
+                    watched.difference(&dirs).for_each(|dir| {
+                        backend.unwatch(dir).ok();
+                    });
+                    dirs.difference(&watched).for_each(|dir| {
+                        if let Err(err) = backend.watch(dir) {
+                            eprintln!("error watching {dir:?}: {err}");
+                        }
+                    });
+                }
+                *outside = files;
+            };
+
+            process(None, &mut outside);
+
+            rx.iter().map_while(|e| e).for_each(|e| match e {
+                Ok(e) => {
+                    let changed: Vec<_> = e
+                        .into_iter()
+                        .map(|e| e.path)
+                        .filter(|path| {
+                            outside.contains(path)
+                                || !path
+                                    .ancestors()
+                                    .take_while(|path| !roots.iter().any(|root| path == root))
+                                    .any(|path| Some(path) == output.as_deref())
+                        })
+                        .collect();
+                    if !changed.is_empty() {
+                        process(Some(&changed), &mut outside);
+                    }
+                }
+                Err(errors) => {
+                    let mut backend = backend.lock().unwrap();
+                    if matches!(&*backend, Some(Backend::Native(_)))
+                        && errors.iter().any(unsupported)
+                    {
+                        eprintln!("watcher failed, falling back to polling: {errors:?}");
+                        backend.take().unwrap().stop();
+                        match Backend::poll(&roots, &options, &events) {
+                            Ok(mut poll) => {
+                                parents(&outside).iter().for_each(|dir| {
+                                    if let Err(err) = poll.watch(dir) {
+                                        eprintln!("error watching {dir:?}: {err}");
+                                    }
+                                });
+                                *backend = Some(poll);
+                            }
+                            Err(err) => eprintln!("error watching: {err}"),
+                        }
+                    } else {
+                        eprintln!("error watching: {errors:?}");
+                    }
+                }
+            });
+        }
     });
+
+    Ok(Watcher {
+        backend,
+        events,
+        thread: Some(thread),
+    })
 }
@@ -0,0 +1,52 @@
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+const ATTEMPTS: usize = 100;
+
+const INTERVAL: Duration = Duration::from_millis(100);
+
+pub fn testdir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+pub fn copy_dir(src: &Path, dst: &Path) {
+    fs::create_dir_all(dst).unwrap();
+    fs::read_dir(src)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .for_each(|path| {
+            let dst = dst.join(path.file_name().unwrap());
+            if path.is_dir() {
+                copy_dir(&path, &dst);
+            } else {
+                fs::copy(&path, dst).unwrap();
+            }
+        });
+}
+
+pub fn wait_until(mut check: impl FnMut() -> bool) {
+    for _ in 0..ATTEMPTS {
+        if check() {
+            return;
+        }
+        thread::sleep(INTERVAL);
+    }
+    panic!("timeout");
+}
+
+pub async fn wait_for<T, F: Future<Output = Option<T>>>(mut check: impl FnMut() -> F) -> T {
+    for _ in 0..ATTEMPTS {
+        if let Some(value) = check().await {
+            return value;
+        }
+        tokio::time::sleep(INTERVAL).await;
+    }
+    panic!("timeout");
+}
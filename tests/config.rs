@@ -0,0 +1,76 @@
+use {
+    std::{fs, net::SocketAddr, path::PathBuf, process::Command},
+    symo::{Config, Strictness},
+};
+
+#[test]
+fn discover() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let nested = tempdir.path().join("a/b");
+    fs::create_dir_all(&nested).unwrap();
+    assert_eq!(Config::discover(&nested).unwrap(), None);
+
+    fs::write(
+        tempdir.path().join("symo.yaml"),
+        "model: model\ntemplate: docs\naddr: 127.0.0.1:8000\nexclude: ['*.txt']\nstrictness: lenient\n",
+    )
+    .unwrap();
+    let config = Config::discover(&nested).unwrap().unwrap();
+    assert_eq!(config.model, [tempdir.path().join("model")]);
+    assert_eq!(config.template, [tempdir.path().join("docs")]);
+    assert_eq!(config.output, None);
+    assert_eq!(config.addr, Some("127.0.0.1:8000".parse().unwrap()));
+    assert_eq!(config.strictness, Some(Strictness::Lenient));
+
+    let cli = Config {
+        template: vec![PathBuf::from("other")],
+        output: Some(PathBuf::from("out")),
+        include: vec!["*.png".to_string()],
+        ..Config::default()
+    };
+    let merged = cli.or(config);
+    assert_eq!(merged.model, [tempdir.path().join("model")]);
+    assert_eq!(merged.template, [PathBuf::from("other")]);
+    assert_eq!(merged.output, Some(PathBuf::from("out")));
+    assert_eq!(merged.addr, Some(SocketAddr::from(([127, 0, 0, 1], 8000))));
+    assert_eq!(merged.include, ["*.png"]);
+    assert_eq!(merged.exclude, ["*.txt"]);
+    assert_eq!(merged.options().extensions, ["md"]);
+    assert!(merged.options().banner);
+}
+
+#[test]
+fn invalid() {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::write(tempdir.path().join("symo.yaml"), "models: model\n").unwrap();
+    assert!(matches!(
+        Config::discover(tempdir.path()),
+        Err(symo::Error::Config { .. })
+    ));
+}
+
+#[test]
+fn cli_uses_config() {
+    let testdir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let tempdir = tempfile::tempdir().unwrap();
+    let nested = tempdir.path().join("nested");
+    fs::create_dir_all(&nested).unwrap();
+    fs::write(
+        tempdir.path().join("symo.yaml"),
+        format!(
+            "model: {:?}\ntemplate: {:?}\noutput: output\nexclude: ['*.txt']\n",
+            testdir.join("data-model"),
+            testdir.join("data-template"),
+        ),
+    )
+    .unwrap();
+    fs::create_dir_all(tempdir.path().join("output")).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_symo"))
+        .current_dir(&nested)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(tempdir.path().join("output/sample1.md").exists());
+    assert!(tempdir.path().join("output/image.svg").exists());
+    assert!(!tempdir.path().join("output/notes.txt").exists());
+}
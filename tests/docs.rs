@@ -1,7 +1,7 @@
 use {
     similar::TextDiff,
-    std::{fs, path::PathBuf},
-    symo::Model,
+    std::{fs, path::PathBuf, slice},
+    symo::{Model, Options},
 };
 
 #[tokio::test]
@@ -11,10 +11,16 @@ async fn self_doc() {
     let model = repodir.join("model");
     let tempdir = tempfile::tempdir().unwrap();
     let output = tempdir.path().to_owned();
-    symo::run_one_time(&model, &template, &output).unwrap();
+    symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &output,
+        &Options::default(),
+    )
+    .unwrap();
     let readme_diff = TextDiff::from_lines(
-        &String::from_utf8(fs::read(&repodir.join("README.md")).unwrap()).unwrap(),
-        &String::from_utf8(fs::read(&output.join("README.md")).unwrap()).unwrap(),
+        &String::from_utf8(fs::read(repodir.join("README.md")).unwrap()).unwrap(),
+        &String::from_utf8(fs::read(output.join("README.md")).unwrap()).unwrap(),
     )
     .unified_diff()
     .to_string();
@@ -29,22 +35,39 @@ async fn sample_generate() {
     let output = testdir.join("data-output");
     let tempdir = tempfile::tempdir().unwrap();
     let tempoutput = tempdir.path().to_owned();
-    symo::run_one_time(&model, &template, &tempoutput).unwrap();
+    symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &tempoutput,
+        &Options::default(),
+    )
+    .unwrap();
     let sample1_diff = TextDiff::from_lines(
-        &String::from_utf8(fs::read(&tempoutput.join("sample1.md")).unwrap()).unwrap(),
-        &String::from_utf8(fs::read(&output.join("sample1.md")).unwrap()).unwrap(),
+        &String::from_utf8(fs::read(tempoutput.join("sample1.md")).unwrap()).unwrap(),
+        &String::from_utf8(fs::read(output.join("sample1.md")).unwrap()).unwrap(),
     )
     .unified_diff()
     .to_string();
     assert_eq!(sample1_diff, "");
 
     let sample2_diff = TextDiff::from_lines(
-        &String::from_utf8(fs::read(&tempoutput.join("sample2.md")).unwrap()).unwrap(),
-        &String::from_utf8(fs::read(&output.join("sample2.md")).unwrap()).unwrap(),
+        &String::from_utf8(fs::read(tempoutput.join("sample2.md")).unwrap()).unwrap(),
+        &String::from_utf8(fs::read(output.join("sample2.md")).unwrap()).unwrap(),
     )
     .unified_diff()
     .to_string();
     assert_eq!(sample2_diff, "");
+
+    let sample3_diff = TextDiff::from_lines(
+        &String::from_utf8(fs::read(tempoutput.join("sample3-renamed.md")).unwrap()).unwrap(),
+        &String::from_utf8(fs::read(output.join("sample3-renamed.md")).unwrap()).unwrap(),
+    )
+    .unified_diff()
+    .to_string();
+    assert_eq!(sample3_diff, "");
+    assert!(!tempoutput.join("sample3.md").exists());
+    assert!(!tempoutput.join("draft.md").exists());
+    assert!(!tempoutput.join("_layouts").exists());
 }
 
 #[tokio::test]
@@ -68,3 +91,715 @@ async fn sample_model() {
     assert!(model.diagrams.contains_key("diagram1"));
     assert!(model.diagrams.contains_key("diagram2"));
 }
+
+#[tokio::test]
+async fn sample_assets() {
+    let testdir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let model = testdir.join("data-model");
+    let tempdir = tempfile::tempdir().unwrap();
+    let template = tempdir.path().join("template");
+    let output = tempdir.path().join("output");
+    fs::create_dir_all(&template).unwrap();
+    fs::create_dir_all(&output).unwrap();
+    ["sample1.md", "image.svg", "notes.txt"]
+        .iter()
+        .for_each(|name| {
+            fs::copy(
+                testdir.join("data-template").join(name),
+                template.join(name),
+            )
+            .unwrap();
+        });
+    fs::write(output.join("keep.txt"), "keep").unwrap();
+    let options = Options {
+        exclude: vec!["*.txt".to_string()],
+        ..Options::default()
+    };
+
+    symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &output,
+        &options,
+    )
+    .unwrap();
+    assert!(output.join("sample1.md").exists());
+    assert!(!output.join("notes.txt").exists());
+    assert_eq!(
+        fs::read(output.join("image.svg")).unwrap(),
+        fs::read(template.join("image.svg")).unwrap()
+    );
+    assert_eq!(
+        fs::metadata(output.join("image.svg"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        fs::metadata(template.join("image.svg"))
+            .unwrap()
+            .modified()
+            .unwrap()
+    );
+
+    fs::remove_file(template.join("image.svg")).unwrap();
+    fs::remove_file(template.join("sample1.md")).unwrap();
+    symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &output,
+        &options,
+    )
+    .unwrap();
+    assert!(!output.join("image.svg").exists());
+    assert!(!output.join("sample1.md").exists());
+    assert!(output.join("keep.txt").exists());
+}
+
+#[tokio::test]
+async fn sample_nested() {
+    let testdir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let model = testdir.join("data-model");
+    let tempdir = tempfile::tempdir().unwrap();
+    let template = tempdir.path().join("template");
+    let output = tempdir.path().join("output");
+    fs::create_dir_all(template.join("sub").join("dir")).unwrap();
+    fs::create_dir_all(output.join("other")).unwrap();
+    fs::copy(
+        testdir.join("data-template").join("sample2.md"),
+        template.join("sub").join("dir").join("sample2.md"),
+    )
+    .unwrap();
+    fs::copy(
+        testdir.join("data-template").join("image.svg"),
+        template.join("sub").join("image.svg"),
+    )
+    .unwrap();
+    fs::write(output.join("other").join("keep.md"), "keep").unwrap();
+
+    symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &output,
+        &Options::default(),
+    )
+    .unwrap();
+    let sample2_diff = TextDiff::from_lines(
+        &String::from_utf8(fs::read(output.join("sub").join("dir").join("sample2.md")).unwrap())
+            .unwrap(),
+        &String::from_utf8(fs::read(testdir.join("data-output").join("sample2.md")).unwrap())
+            .unwrap(),
+    )
+    .unified_diff()
+    .to_string();
+    assert_eq!(sample2_diff, "");
+    assert!(output.join("sub").join("image.svg").exists());
+
+    fs::rename(
+        template.join("sub").join("dir").join("sample2.md"),
+        template.join("sub").join("renamed.md"),
+    )
+    .unwrap();
+    symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &output,
+        &Options::default(),
+    )
+    .unwrap();
+    assert!(!output.join("sub").join("dir").exists());
+    assert!(output.join("sub").join("renamed.md").exists());
+    assert!(output.join("sub").join("image.svg").exists());
+    assert!(output.join("other").join("keep.md").exists());
+}
+
+#[tokio::test]
+async fn sample_unchanged() {
+    let testdir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let template = testdir.join("data-template");
+    let model = testdir.join("data-model");
+    let tempdir = tempfile::tempdir().unwrap();
+    let output = tempdir.path().to_owned();
+
+    let summary = symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &output,
+        &Options::default(),
+    )
+    .unwrap();
+    assert_eq!(summary.created.len(), 5);
+    assert!(summary.updated.is_empty());
+    let mtime = fs::metadata(output.join("sample1.md"))
+        .unwrap()
+        .modified()
+        .unwrap();
+
+    fs::write(output.join("sample2.md"), "outdated").unwrap();
+    let summary = symo::run_one_time(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        &output,
+        &Options::default(),
+    )
+    .unwrap();
+    assert!(summary.created.is_empty());
+    assert_eq!(summary.updated, vec![PathBuf::from("sample2.md")]);
+    assert_eq!(summary.unchanged.len(), 4);
+    assert!(summary.removed.is_empty());
+    assert_eq!(
+        fs::metadata(output.join("sample1.md"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        mtime
+    );
+}
+
+#[tokio::test]
+async fn sample_layered() {
+    let testdir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let template = tempdir.path().join("template");
+    let output = tempdir.path().join("output");
+    fs::create_dir_all(&model).unwrap();
+    fs::create_dir_all(template.join("_layouts")).unwrap();
+    fs::create_dir_all(&output).unwrap();
+    fs::write(
+        model.join("nodes.yaml"),
+        "nodes:\n  node1:\n    parent: template1\n    name: node1\n    description: project\n",
+    )
+    .unwrap();
+    fs::write(template.join("sample2.md"), "project\n").unwrap();
+    fs::write(
+        template.join("_layouts").join("page.md"),
+        "layout\n{{{body}}}",
+    )
+    .unwrap();
+
+    symo::run_one_time(
+        &[testdir.join("data-model"), model],
+        &[testdir.join("data-template"), template],
+        &output,
+        &Options::default(),
+    )
+    .unwrap();
+    assert!(fs::read_to_string(output.join("sample1.md"))
+        .unwrap()
+        .contains("node-template1: node1, node1, project\n"));
+    assert_eq!(
+        fs::read_to_string(output.join("sample2.md")).unwrap(),
+        "<!-- DO NOT EDIT; Autogenerated -->\n\nproject\n"
+    );
+    assert!(fs::read_to_string(output.join("sample3-renamed.md"))
+        .unwrap()
+        .starts_with("<!-- DO NOT EDIT; Autogenerated -->\n\nlayout\n"));
+    assert!(output.join("image.svg").exists());
+}
+
+#[tokio::test]
+async fn sample_imports() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    ["model", "shared", "vendor/lib", "template", "output"]
+        .iter()
+        .for_each(|name| fs::create_dir_all(dir.join(name)).unwrap());
+    fs::write(
+        dir.join("vendor/lib/base.yaml"),
+        "nodes:\n  template0:\n    definition: 'node: {{id}}, {{name}}'\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("shared/common.yaml"),
+        "imports: ['@lib']\nnodes:\n  b:\n    parent: template0\n    name: B\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("model/main.yaml"),
+        "imports: ['../shared/common.yaml', '@lib/base.yaml']\nnodes:\n  a:\n    parent: template0\n    name: A\ndiagrams:\n  d: |\n    a\n    b\n    {{{definitions}}}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("template/page.md"), "{{{d}}}").unwrap();
+    let options = Options {
+        packages: [("lib".to_string(), dir.join("vendor/lib"))].into(),
+        ..Options::default()
+    };
+    symo::run_one_time(
+        &[dir.join("model")],
+        &[dir.join("template")],
+        &dir.join("output"),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("output/page.md")).unwrap(),
+        "<!-- DO NOT EDIT; Autogenerated -->\n\na\nb\n\nnode: a, A\nnode: b, B\n"
+    );
+
+    assert!(matches!(
+        Model::new(&dir.join("model")),
+        Err(symo::Error::ModelHasUnknownPackage { .. })
+    ));
+
+    fs::write(
+        dir.join("shared/common.yaml"),
+        "imports: ['../model/main.yaml']\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        Model::new(&dir.join("model")),
+        Err(symo::Error::ModelImportCycle { .. })
+    ));
+}
+
+#[tokio::test]
+async fn sample_namespaces() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    ["model/payments", "template", "output"]
+        .iter()
+        .for_each(|name| fs::create_dir_all(dir.join(name)).unwrap());
+    fs::write(
+        dir.join("model/base.yaml"),
+        "nodes:\n  template0:\n    definition: '{{id}}[{{name}}]'\nrelations:\n  uses:\n    definition: '{{left}} --> {{right}}'\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("model/payments/nodes.yaml"),
+        "nodes:\n  database:\n    parent: template0\n    name: payments db\n  api:\n    parent: template0\n    name: payments api\n    relations:\n      database:\n        - parent: uses\ndiagrams:\n  overview: |\n    api\n    database\n    {{{definitions}}}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("model/orders.yaml"),
+        "namespace: orders\nnodes:\n  database:\n    parent: template0\n    name: orders db\n    relations:\n      payments::database:\n        - parent: uses\ndiagrams:\n  overview: |\n    database\n    payments::database\n    {{{definitions}}}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("template/page.md"),
+        "{{{[payments::overview]}}}----\n{{{[orders::overview]}}}",
+    )
+    .unwrap();
+
+    let model = Model::new(&dir.join("model")).unwrap();
+    assert!(model.nodes.contains_key("payments::database"));
+    assert!(model.nodes.contains_key("orders::database"));
+    assert!(model.diagrams.contains_key("orders::overview"));
+
+    symo::run_one_time(
+        &[dir.join("model")],
+        &[dir.join("template")],
+        &dir.join("output"),
+        &Options::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("output/page.md")).unwrap(),
+        "<!-- DO NOT EDIT; Autogenerated -->\n\n\
+        payments__api\npayments__database\n\n\
+        payments__api[payments api]\npayments__api --> payments__database\n\
+        payments__database[payments db]\n\
+        ----\n\
+        orders__database\npayments__database\n\n\
+        orders__database[orders db]\norders__database --> payments__database\n\
+        payments__database[payments db]\n"
+    );
+
+    fs::create_dir_all(dir.join("model/global")).unwrap();
+    fs::write(
+        dir.join("model/global/nodes.yaml"),
+        "namespace: ''\nnodes:\n  shared:\n    parent: template0\n",
+    )
+    .unwrap();
+    assert!(Model::new(&dir.join("model"))
+        .unwrap()
+        .nodes
+        .contains_key("shared"));
+
+    fs::write(
+        dir.join("model/legacy.yaml"),
+        "nodes:\n  payments__database:\n    parent: template0\n",
+    )
+    .unwrap();
+    assert!(matches!(
+        Model::new(&dir.join("model")),
+        Err(symo::Error::NodeHasReservedId { list }) if list == ["payments__database"]
+    ));
+}
+
+#[tokio::test]
+async fn sample_formats() {
+    let testdir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let output = tempdir.path().join("output");
+    fs::create_dir_all(&model).unwrap();
+    fs::create_dir_all(&output).unwrap();
+    fs::copy(
+        testdir.join("data-model").join("diagrams.yaml"),
+        model.join("diagrams.yml"),
+    )
+    .unwrap();
+    fs::copy(
+        testdir.join("data-model").join("nodes.yaml"),
+        model.join("nodes.yaml"),
+    )
+    .unwrap();
+    fs::write(
+        model.join("relations.toml"),
+        "[relations.base-tag0]\n\
+        definition = 'relation-tag0: {{left}}, {{description}}, {{technology}}, {{right}}'\n\
+        tags = ['tag0']\n\n\
+        [relations.base-tag1]\n\
+        definition = 'relation-tag1: {{left}}, {{description}}, {{technology}}, {{right}}'\n\
+        tags = ['tag1']\n",
+    )
+    .unwrap();
+    fs::write(
+        model.join("extra.json"),
+        r#"{"nodes": {"json-node": {"parent": "template0", "name": "json"}}}"#,
+    )
+    .unwrap();
+    fs::write(model.join("notes.txt"), "not a model").unwrap();
+
+    let loaded = Model::new(&model).unwrap();
+    assert_eq!(loaded.nodes["json-node"].name.as_deref(), Some("json"));
+    assert_eq!(loaded.relations.len(), 2);
+    assert_eq!(loaded.diagrams.len(), 3);
+
+    symo::run_one_time(
+        &[model],
+        &[testdir.join("data-template")],
+        &output,
+        &Options::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(output.join("sample1.md")).unwrap(),
+        fs::read_to_string(testdir.join("data-output").join("sample1.md")).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn sample_extensions() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    ["model", "template/_layouts", "output"]
+        .iter()
+        .for_each(|name| fs::create_dir_all(dir.join(name)).unwrap());
+    fs::write(
+        dir.join("model/nodes.yaml"),
+        "nodes:\n  a:\n    definition: 'node: {{id}}'\ndiagrams:\n  d: |\n    flowchart\n    a\n",
+    )
+    .unwrap();
+    fs::write(dir.join("template/d.mmd.hbs"), "{{{d}}}").unwrap();
+    fs::write(
+        dir.join("template/page.html.hbs"),
+        "---\nlayout: base\n---\n<pre>{{{d}}}</pre>",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("template/_layouts/base.html"),
+        "<body>{{{body}}}</body>",
+    )
+    .unwrap();
+    fs::write(dir.join("template/doc.adoc"), "{{{d}}}").unwrap();
+    fs::write(dir.join("template/raw.rst"), "{{{d}}}").unwrap();
+    fs::write(
+        dir.join("template/feed.xml.hbs"),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed/>\n",
+    )
+    .unwrap();
+    let options = Options {
+        extensions: vec!["md".to_string(), ".adoc".to_string()],
+        ..Options::default()
+    };
+    symo::run_one_time(
+        &[dir.join("model")],
+        &[dir.join("template")],
+        &dir.join("output"),
+        &options,
+    )
+    .unwrap();
+    let read = |name: &str| fs::read_to_string(dir.join("output").join(name)).unwrap();
+    assert_eq!(
+        read("d.mmd"),
+        "%% DO NOT EDIT; Autogenerated\n\nflowchart\na\n"
+    );
+    assert_eq!(
+        read("page.html"),
+        "<!-- DO NOT EDIT; Autogenerated -->\n\n<body><pre>flowchart\na\n</pre></body>"
+    );
+    assert_eq!(
+        read("doc.adoc"),
+        "// DO NOT EDIT; Autogenerated\n\nflowchart\na\n"
+    );
+    assert_eq!(read("raw.rst"), "{{{d}}}");
+    assert_eq!(
+        read("feed.xml"),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!-- DO NOT EDIT; Autogenerated -->\n<feed/>\n"
+    );
+    assert!(!dir.join("output/d.mmd.hbs").exists());
+
+    let options = Options {
+        banner: false,
+        ..options
+    };
+    symo::run_one_time(
+        &[dir.join("model")],
+        &[dir.join("template")],
+        &dir.join("output"),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(read("d.mmd"), "flowchart\na\n");
+}
+
+#[tokio::test]
+async fn sample_export() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    ["model/payments", "template", "output"]
+        .iter()
+        .for_each(|name| fs::create_dir_all(dir.join(name)).unwrap());
+    fs::write(
+        dir.join("model/payments/nodes.yaml"),
+        "nodes:\n  db:\n    definition: 'node: {{id}}'\ndiagrams:\n  overview: |\n    ```mermaid\n    flowchart\n    db\n    ```\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("model/main.yaml"),
+        "diagrams:\n  main: a\n  api.v1: b\n  api.v2: c\n",
+    )
+    .unwrap();
+    fs::write(dir.join("template/page.md"), "page").unwrap();
+    let options = Options {
+        export: Some(PathBuf::from("diagrams")),
+        ..Options::default()
+    };
+    symo::run_one_time(
+        &[dir.join("model")],
+        &[dir.join("template")],
+        &dir.join("output"),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("output/diagrams/payments/overview.mmd")).unwrap(),
+        "%% DO NOT EDIT; Autogenerated\n\nflowchart\npayments__db\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("output/diagrams/main.mmd")).unwrap(),
+        "%% DO NOT EDIT; Autogenerated\n\na\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("output/diagrams/api.v1.mmd")).unwrap(),
+        "%% DO NOT EDIT; Autogenerated\n\nb\n"
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("output/diagrams/api.v2.mmd")).unwrap(),
+        "%% DO NOT EDIT; Autogenerated\n\nc\n"
+    );
+
+    fs::remove_file(dir.join("model/main.yaml")).unwrap();
+    symo::run_one_time(
+        &[dir.join("model")],
+        &[dir.join("template")],
+        &dir.join("output"),
+        &options,
+    )
+    .unwrap();
+    assert!(!dir.join("output/diagrams/main.mmd").exists());
+    assert!(dir.join("output/diagrams/payments/overview.mmd").exists());
+
+    ["../../x", "/x", "a::..::..::x"].iter().for_each(|name| {
+        fs::write(
+            dir.join("model/main.yaml"),
+            format!("diagrams:\n  {name:?}: a\n"),
+        )
+        .unwrap();
+        assert!(matches!(
+            symo::run_one_time(
+                &[dir.join("model")],
+                &[dir.join("template")],
+                &dir.join("output"),
+                &options,
+            ),
+            Err(symo::Error::ExportHasInvalidPath { .. })
+        ));
+    });
+    assert!(!dir.join("x.mmd").exists());
+    fs::remove_file(dir.join("model/main.yaml")).unwrap();
+
+    let options = Options {
+        export: Some(PathBuf::from("../diagrams")),
+        ..options
+    };
+    assert!(matches!(
+        symo::run_one_time(
+            &[dir.join("model")],
+            &[dir.join("template")],
+            &dir.join("output"),
+            &options,
+        ),
+        Err(symo::Error::ExportHasInvalidPath { .. })
+    ));
+}
+
+#[tokio::test]
+async fn sample_svg() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    ["model", "template/sub", "output"]
+        .iter()
+        .for_each(|name| fs::create_dir_all(dir.join(name)).unwrap());
+    fs::write(
+        dir.join("model/model.yaml"),
+        r#"nodes:
+  user:
+    definition: '{{id}}(("{{name}}<br/>[Person]"))'
+    name: User
+  app:
+    definition: '{{id}}["{{name}}"]'
+    name: App & API
+  db:
+    definition: '{{id}}[("{{name}}")]'
+    name: Database
+diagrams:
+  overview: |
+    ```mermaid
+    flowchart LR
+    user
+    subgraph "backend"
+      app
+      db
+    end
+    {{{definitions}}}
+    user -->|"Uses"| app
+    app -.->|"Reads"| db
+    ```
+  sequence: |
+    ```mermaid
+    sequenceDiagram
+    user->>app: call
+    ```
+  api.v1: |
+    flowchart LR
+    a --> b
+  api.v2: |
+    flowchart LR
+    c --> d
+"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("template/page.md"),
+        "{{{overview}}}\n{{{sequence}}}",
+    )
+    .unwrap();
+    fs::write(dir.join("template/sub/page.md"), "{{{overview}}}").unwrap();
+    fs::write(dir.join("template/page.html.hbs"), "{{{overview}}}").unwrap();
+    let options = Options {
+        svg: Some(PathBuf::from("diagrams")),
+        ..Options::default()
+    };
+    let run = |options: &Options| {
+        symo::run_one_time(
+            &[dir.join("model")],
+            &[dir.join("template")],
+            &dir.join("output"),
+            options,
+        )
+        .unwrap()
+    };
+    let read = |name: &str| fs::read_to_string(dir.join("output").join(name)).unwrap();
+
+    run(&options);
+    let svg = read("diagrams/overview.svg");
+    assert!(svg.starts_with("<!-- DO NOT EDIT; Autogenerated -->\n\n<svg "));
+    assert!(svg.ends_with("</svg>\n"));
+    [
+        "<circle ",
+        ">User</text>",
+        ">[Person]</text>",
+        ">App &amp; API</text>",
+        ">Database</text>",
+        ">backend</text>",
+        ">Uses</text>",
+        "stroke-dasharray",
+        "marker-end",
+    ]
+    .iter()
+    .for_each(|part| assert!(svg.contains(part), "{part}"));
+    assert!(!dir.join("output/diagrams/sequence.svg").exists());
+    assert!(read("diagrams/api.v1.svg").contains(">b</text>"));
+    assert!(read("diagrams/api.v2.svg").contains(">d</text>"));
+    assert!(read("page.md").contains("flowchart LR"));
+
+    let options = Options {
+        svg_links: true,
+        ..options
+    };
+    run(&options);
+    assert_eq!(read("diagrams/overview.svg"), svg);
+    let page = read("page.md");
+    assert!(page.contains("![overview](diagrams/overview.svg)\n"));
+    assert!(page.contains("sequenceDiagram"));
+    assert!(read("sub/page.md").contains("![overview](../diagrams/overview.svg)"));
+    assert!(read("page.html").contains("<img src=\"diagrams/overview.svg\" alt=\"overview\">"));
+}
+
+#[tokio::test]
+async fn sample_stdlib() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    ["model", "template", "output"]
+        .iter()
+        .for_each(|name| fs::create_dir_all(dir.join(name)).unwrap());
+    fs::write(
+        dir.join("model/a.yaml"),
+        "nodes:\n  template-person:\n    definition: '{{id}}((\"{{name}}\"))'\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("model/b.yaml"),
+        r#"imports: ['@symo/c4']
+nodes:
+  user:
+    parent: template-person
+    name: User
+    relations:
+      db:
+        - parent: reads
+  db:
+    parent: template-database
+    name: Store
+    technology: Postgres
+diagrams:
+  d: |
+    user
+    db
+    {{{definitions c4model}}}
+"#,
+    )
+    .unwrap();
+    fs::write(dir.join("template/page.md"), "{{{d}}}").unwrap();
+    symo::run_one_time(
+        &[dir.join("model")],
+        &[dir.join("template")],
+        &dir.join("output"),
+        &Options::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.join("output/page.md")).unwrap(),
+        "<!-- DO NOT EDIT; Autogenerated -->\n\nuser\ndb\n\n\
+        db[(\"Store<br/>[Database: Postgres]\")]\n\
+        user((\"User\"))\n\
+        user -.->|\"Reads\"| db\n"
+    );
+
+    fs::write(dir.join("model/b.yaml"), "imports: ['@symo/c5']\n").unwrap();
+    assert!(matches!(
+        Model::new(&dir.join("model")),
+        Err(symo::Error::ModelHasUnknownLibrary { .. })
+    ));
+}
@@ -0,0 +1,92 @@
+use {
+    std::{fs, process::Command},
+    symo::{Config, Kit},
+};
+
+#[test]
+fn kits() {
+    [(Kit::C4, "customer -.->|"), (Kit::Dataflow, "sensors ==>|")]
+        .iter()
+        .for_each(|(kit, relation)| {
+            let tempdir = tempfile::tempdir().unwrap();
+            let dir = tempdir.path();
+            let files = symo::init(dir, *kit).unwrap();
+            assert!(files.contains(&dir.join("symo.yaml")));
+            assert_eq!(
+                fs::read_to_string(dir.join(".gitignore")).unwrap(),
+                "/site/\n"
+            );
+
+            let config = Config::load(&dir.join("symo.yaml")).unwrap();
+            symo::run_one_time(
+                &config.model,
+                &config.template,
+                config.output.as_ref().unwrap(),
+                &config.options(),
+            )
+            .unwrap();
+            let readme = fs::read_to_string(dir.join("site/README.md")).unwrap();
+            assert!(readme.contains("```mermaid\nflowchart LR\n"));
+            assert!(readme.contains(relation), "{readme}");
+        });
+}
+
+#[test]
+fn existing_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path();
+    fs::create_dir_all(dir.join("docs")).unwrap();
+    fs::write(dir.join("docs/README.md"), "mine").unwrap();
+    assert!(matches!(
+        symo::init(dir, Kit::C4),
+        Err(symo::Error::InitFileExists { path }) if path == dir.join("docs/README.md")
+    ));
+    assert!(!dir.join("symo.yaml").exists());
+    assert_eq!(
+        fs::read_to_string(dir.join("docs/README.md")).unwrap(),
+        "mine"
+    );
+}
+
+#[test]
+fn existing_gitignore() {
+    [
+        ("target\n", "target\n/site/\n"),
+        ("target", "target\n/site/\n"),
+        ("/site/\ntarget\n", "/site/\ntarget\n"),
+    ]
+    .iter()
+    .for_each(|(existing, expected)| {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dir = tempdir.path();
+        fs::write(dir.join(".gitignore"), existing).unwrap();
+        let files = symo::init(dir, Kit::C4).unwrap();
+        assert_eq!(
+            files.contains(&dir.join(".gitignore")),
+            existing != expected
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join(".gitignore")).unwrap(),
+            *expected
+        );
+    });
+}
+
+#[test]
+fn cli() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let dir = tempdir.path().join("project");
+    let status = Command::new(env!("CARGO_BIN_EXE_symo"))
+        .args(["init", dir.to_str().unwrap(), "--kit", "dataflow"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new(env!("CARGO_BIN_EXE_symo"))
+        .current_dir(&dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(fs::read_to_string(dir.join("site/README.md"))
+        .unwrap()
+        .contains("ingest ==>|\"Measurements\"| measurements"));
+}
@@ -0,0 +1,486 @@
+mod common;
+
+use {
+    common::{copy_dir, testdir, wait_for},
+    std::{
+        fs,
+        future::ready,
+        net::{SocketAddr, TcpListener},
+        path::PathBuf,
+        time::Duration,
+    },
+    symo::Options,
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    },
+};
+
+async fn request_with(addr: &SocketAddr, path: &str, headers: &[String]) -> TcpStream {
+    let mut stream = wait_for(|| async { TcpStream::connect(addr).await.ok() }).await;
+    stream
+        .write_all(
+            format!(
+                "GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n",
+                headers
+                    .iter()
+                    .map(|header| format!("{header}\r\n"))
+                    .collect::<String>()
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    stream
+}
+
+async fn request(addr: &SocketAddr, path: &str) -> TcpStream {
+    request_with(addr, path, &[]).await
+}
+
+async fn get_with(addr: &SocketAddr, path: &str, headers: &[String]) -> String {
+    let mut response = String::new();
+    request_with(addr, path, headers)
+        .await
+        .read_to_string(&mut response)
+        .await
+        .unwrap();
+    response
+}
+
+async fn get(addr: &SocketAddr, path: &str) -> String {
+    get_with(addr, path, &[]).await
+}
+
+struct Server {
+    _tempdir: tempfile::TempDir,
+    addr: SocketAddr,
+    model: PathBuf,
+    template: PathBuf,
+    output: PathBuf,
+}
+
+async fn start() -> Server {
+    let testdir = testdir();
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let template = tempdir.path().join("template");
+    let output = tempdir.path().join("output");
+    copy_dir(&testdir.join("data-model"), &model);
+    copy_dir(&testdir.join("data-template"), &template);
+    fs::create_dir_all(&output).unwrap();
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    tokio::spawn({
+        let (model, template, output) = (model.clone(), template.clone(), output.clone());
+        async move {
+            symo::run_serve(
+                &[model],
+                &[template],
+                Some(&output),
+                &addr,
+                &Options::default(),
+            )
+            .await
+            .unwrap();
+        }
+    });
+    wait_for(|| ready(output.join(".symo-manifest").exists().then_some(()))).await;
+    Server {
+        _tempdir: tempdir,
+        addr,
+        model,
+        template,
+        output,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn live_reload() {
+    let server = start().await;
+
+    let listing = get(&server.addr, "/").await;
+    assert!(listing.contains("text/html"));
+    assert!(listing.contains("new EventSource(\"/_symo/events\")"));
+
+    let mut events = request(&server.addr, "/_symo/events").await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    fs::write(server.template.join("sample2.md"), "changed\n").unwrap();
+    let mut received = String::new();
+    let mut buf = [0; 1024];
+    while !received.contains("data:") {
+        let len = tokio::time::timeout(Duration::from_secs(10), events.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(len, 0);
+        received.push_str(&String::from_utf8_lossy(&buf[..len]));
+    }
+    assert!(received.contains("event:reload\n"));
+    assert!(received.contains("data:[\"/sample2.md\"]\n"));
+    assert!(server.output.join("sample2.md").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn build_error_overlay() {
+    let server = start().await;
+    assert!(!get(&server.addr, "/").await.contains("symo build failed"));
+
+    fs::write(server.template.join("sample2.md"), "{{unknown-diagram}}\n").unwrap();
+    let listing = wait_for(|| async {
+        let listing = get(&server.addr, "/").await;
+        listing.contains("symo build failed").then_some(listing)
+    })
+    .await;
+    assert!(listing.contains("unknown-diagram"));
+
+    fs::write(server.template.join("sample2.md"), "fixed\n").unwrap();
+    wait_for(|| async {
+        (!get(&server.addr, "/").await.contains("symo build failed")).then_some(())
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn diagram_error_overlay() {
+    let server = start().await;
+    let diagrams = server.model.join("diagrams.yaml");
+    let original = fs::read_to_string(&diagrams).unwrap();
+
+    fs::write(&diagrams, format!("{original}    {{{{nme}}}}\n")).unwrap();
+    let listing = wait_for(|| async {
+        let listing = get(&server.addr, "/").await;
+        listing.contains("symo build failed").then_some(listing)
+    })
+    .await;
+    assert!(listing.contains("&quot;diagram2&quot;"));
+    assert!(listing.contains("nme"));
+
+    fs::write(&diagrams, &original).unwrap();
+    wait_for(|| async {
+        (!get(&server.addr, "/").await.contains("symo build failed")).then_some(())
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn markdown_to_html() {
+    let server = start().await;
+    fs::write(
+        server.template.join("page.md"),
+        "# Title\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n```mermaid\nflowchart LR\na --> b\n```\n",
+    )
+    .unwrap();
+    wait_for(|| ready(server.output.join("page.md").exists().then_some(()))).await;
+
+    let page = get(&server.addr, "/page.md").await;
+    assert!(page.contains("text/html"));
+    assert!(page.contains("<h1>Title</h1>"));
+    assert!(page.contains("<table>"));
+    assert!(page.contains("<pre class=\"mermaid\">flowchart LR\na --&gt; b\n</pre>"));
+    assert!(page.contains("<script src=\"/_symo/mermaid.min.js\"></script>"));
+    assert!(page.contains("new EventSource"));
+
+    let raw = get(&server.addr, "/page.md?raw").await;
+    assert!(!raw.contains("text/html"));
+    assert!(raw.contains("```mermaid\nflowchart LR\na --> b\n```\n"));
+
+    let script = get(&server.addr, "/_symo/mermaid.min.js").await;
+    assert!(script.contains("application/javascript"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn directory_index() {
+    let server = start().await;
+    let sub = server.output.join("sub");
+    fs::create_dir_all(sub.join("nested")).unwrap();
+    fs::write(sub.join("a&b <c>.txt"), "text").unwrap();
+    fs::write(sub.join("it's.txt"), "text").unwrap();
+    fs::write(sub.join("README.md"), "# Landing\n").unwrap();
+    fs::write(sub.join("upload.tmp"), "partial").unwrap();
+    fs::write(sub.join(".README.md.symo-tmp"), "partial").unwrap();
+
+    let redirect = get(&server.addr, "/sub?x=1").await;
+    assert!(redirect.starts_with("HTTP/1.1 301"));
+    assert!(redirect.contains("location: /sub/?x=1\r\n"));
+
+    let listing = get(&server.addr, "/sub/").await;
+    assert!(listing.starts_with("HTTP/1.1 200"));
+    assert!(listing.contains("<a href=\"../\">../</a>"));
+    assert!(listing
+        .contains("<a href=\"a%26b%20%3Cc%3E.txt\">a&amp;b &lt;c&gt;.txt</a></td><td>4</td>"));
+    assert!(listing.contains("<a href=\"it%27s.txt\">"));
+    assert!(!listing.contains("upload.tmp"));
+    assert!(!listing.contains("symo-tmp"));
+    assert!(listing.contains("<h1>Landing</h1>"));
+    let nested = listing.find("nested/</a>").unwrap();
+    let readme = listing.find("README.md</a>").unwrap();
+    assert!(nested < readme);
+
+    let root = get(&server.addr, "/").await;
+    assert!(!root.contains("../"));
+    assert!(root.contains("<a href=\"sub/\">sub/</a>"));
+    assert!(!root.contains(".symo-manifest"));
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn path_hardening() {
+    let server = start().await;
+    let secret = server.output.parent().unwrap().join("secret.txt");
+    fs::write(&secret, "secret").unwrap();
+    fs::create_dir_all(server.output.join("sub dir")).unwrap();
+    fs::write(server.output.join("sub dir").join("a b.txt"), "spaces").unwrap();
+    std::os::unix::fs::symlink(&secret, server.output.join("escape.txt")).unwrap();
+    std::os::unix::fs::symlink(
+        server.output.parent().unwrap(),
+        server.output.join("escape-dir"),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(
+        server.output.join("sub dir").join("a b.txt"),
+        server.output.join("inside.txt"),
+    )
+    .unwrap();
+
+    let status = |response: String| response.split(' ').nth(1).unwrap().to_string();
+    assert_eq!(
+        status(get(&server.addr, "/sub%20dir/a%20b.txt").await),
+        "200"
+    );
+    assert!(get(&server.addr, "/sub%20dir/a%20b.txt")
+        .await
+        .ends_with("spaces"));
+    assert_eq!(status(get(&server.addr, "/inside.txt").await), "200");
+    assert_eq!(status(get(&server.addr, "/../secret.txt").await), "400");
+    assert_eq!(status(get(&server.addr, "/%2e%2e/secret.txt").await), "400");
+    assert_eq!(
+        status(get(&server.addr, "/sub%20dir%2f..%2f..%2fsecret.txt").await),
+        "400"
+    );
+    assert_eq!(status(get(&server.addr, "/%ff").await), "400");
+    assert_eq!(status(get(&server.addr, "/escape.txt").await), "403");
+    assert_eq!(status(get(&server.addr, "/escape-dir/").await), "403");
+    assert_eq!(
+        status(get(&server.addr, "/escape-dir/secret.txt").await),
+        "403"
+    );
+    assert_eq!(status(get(&server.addr, "/missing.txt").await), "404");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn conditional_and_range() {
+    let server = start().await;
+    fs::write(server.output.join("data.txt"), "0123456789").unwrap();
+    let header = |response: &str, name: &str| {
+        response
+            .lines()
+            .find_map(|line| line.strip_prefix(&format!("{name}: ")))
+            .unwrap()
+            .to_string()
+    };
+
+    let full = get(&server.addr, "/data.txt").await;
+    assert!(full.starts_with("HTTP/1.1 200"));
+    assert!(full.contains("accept-ranges: bytes\r\n"));
+    assert!(full.ends_with("0123456789"));
+    let etag = header(&full, "etag");
+    let modified = header(&full, "last-modified");
+
+    assert!(get_with(
+        &server.addr,
+        "/data.txt",
+        &[format!("If-None-Match: {etag}")]
+    )
+    .await
+    .starts_with("HTTP/1.1 304"));
+    assert!(get_with(
+        &server.addr,
+        "/data.txt",
+        &[format!("If-Modified-Since: {modified}")]
+    )
+    .await
+    .starts_with("HTTP/1.1 304"));
+    assert!(get_with(
+        &server.addr,
+        "/data.txt",
+        &["If-None-Match: \"other\"".to_string()]
+    )
+    .await
+    .starts_with("HTTP/1.1 200"));
+
+    let partial = get_with(&server.addr, "/data.txt", &["Range: bytes=2-4".to_string()]).await;
+    assert!(partial.starts_with("HTTP/1.1 206"));
+    assert_eq!(header(&partial, "content-range"), "bytes 2-4/10");
+    assert!(partial.ends_with("\r\n\r\n234"));
+    assert!(
+        get_with(&server.addr, "/data.txt", &["Range: bytes=-3".to_string()])
+            .await
+            .ends_with("\r\n\r\n789")
+    );
+    let unsatisfiable =
+        get_with(&server.addr, "/data.txt", &["Range: bytes=20-".to_string()]).await;
+    assert!(unsatisfiable.starts_with("HTTP/1.1 416"));
+    assert_eq!(header(&unsatisfiable, "content-range"), "bytes */10");
+    assert!(get_with(
+        &server.addr,
+        "/data.txt",
+        &["Range: bytes=2-4".to_string(), format!("If-Range: {etag}")]
+    )
+    .await
+    .starts_with("HTTP/1.1 206"));
+    assert!(get_with(
+        &server.addr,
+        "/data.txt",
+        &[
+            "Range: bytes=2-4".to_string(),
+            "If-Range: \"other\"".to_string()
+        ]
+    )
+    .await
+    .ends_with("0123456789"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn in_memory_output() {
+    let testdir = testdir();
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let template = tempdir.path().join("template");
+    copy_dir(&testdir.join("data-model"), &model);
+    copy_dir(&testdir.join("data-template"), &template);
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    tokio::spawn({
+        let (model, template) = (model.clone(), template.clone());
+        async move {
+            symo::run_serve(&[model], &[template], None, &addr, &Options::default())
+                .await
+                .unwrap();
+        }
+    });
+    let response = wait_for(|| async {
+        let response = get(&addr, "/sample1.md?raw").await;
+        response.starts_with("HTTP/1.1 200").then_some(response)
+    })
+    .await;
+    assert!(
+        response.ends_with(&fs::read_to_string(testdir.join("data-output/sample1.md")).unwrap())
+    );
+
+    let listing = get(&addr, "/").await;
+    assert!(listing.contains("<a href=\"sample3-renamed.md\">"));
+    assert!(listing.contains("<a href=\"image.svg\">"));
+    assert!(!listing.contains("draft.md"));
+    assert!(get(&addr, "/missing.md").await.starts_with("HTTP/1.1 404"));
+
+    fs::write(template.join("sample2.md"), "changed\n").unwrap();
+    wait_for(|| async {
+        get(&addr, "/sample2.md?raw")
+            .await
+            .ends_with("changed\n")
+            .then_some(())
+    })
+    .await;
+
+    let mut entries: Vec<_> = fs::read_dir(tempdir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    entries.sort();
+    assert_eq!(entries, ["model", "template"]);
+    assert!(!template.join(".symo-manifest").exists());
+}
+
+fn json(response: &str) -> serde_json::Value {
+    serde_json::from_str(response.split_once("\r\n\r\n").unwrap().1).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api() {
+    let server = start().await;
+
+    let nodes = json(&get(&server.addr, "/api/nodes").await);
+    assert_eq!(nodes["node0"]["name"], "node0");
+    assert_eq!(
+        nodes["node0-0"]["definition"],
+        "node-template0: node0-0, node0-0, node0-0-description"
+    );
+
+    let node = json(&get(&server.addr, "/api/nodes/node2").await);
+    assert_eq!(node["description"], "node2-description");
+    assert_eq!(node["outgoing"], serde_json::json!([]));
+    let mut incoming: Vec<_> = node["incoming"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|relation| relation["left"].as_str().unwrap())
+        .collect();
+    incoming.sort();
+    assert_eq!(incoming, ["node0-0", "node1-0"]);
+    assert!(get(&server.addr, "/api/nodes/missing")
+        .await
+        .starts_with("HTTP/1.1 404"));
+
+    let relations = json(&get(&server.addr, "/api/relations").await);
+    assert_eq!(relations.as_array().unwrap().len(), 4);
+
+    let diagram = get(&server.addr, "/api/diagrams/diagram0").await;
+    assert!(diagram.contains("text/plain"));
+    assert!(diagram.contains("relation-tag0: node0-0, node0-0-node2-tag0, , node2\n"));
+    assert!(get(&server.addr, "/api/diagrams/missing")
+        .await
+        .starts_with("HTTP/1.1 404"));
+
+    let status = json(&get(&server.addr, "/api/status").await);
+    assert!(status["built"].is_string());
+    assert!(status["error"].is_null());
+
+    fs::write(server.template.join("broken.md"), "{{missing}}\n").unwrap();
+    let status = wait_for(|| async {
+        let status = json(&get(&server.addr, "/api/status").await);
+        status["error"].is_string().then_some(status)
+    })
+    .await;
+    assert!(status["error"].as_str().unwrap().contains("missing"));
+    assert!(status["succeeded"].is_string());
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread")]
+async fn graceful_shutdown() {
+    let testdir = testdir();
+    let tempdir = tempfile::tempdir().unwrap();
+    let output = tempdir.path().join("output");
+    fs::create_dir_all(&output).unwrap();
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_symo"))
+        .arg(testdir.join("data-model"))
+        .arg(testdir.join("data-template"))
+        .arg(&output)
+        .arg("-s")
+        .arg("-a")
+        .arg(addr.to_string())
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    wait_for(|| ready(output.join(".symo-manifest").exists().then_some(()))).await;
+    let _events = request(&addr, "/_symo/events").await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert!(std::process::Command::new("kill")
+        .arg("-TERM")
+        .arg(child.id().to_string())
+        .status()
+        .unwrap()
+        .success());
+    let status = wait_for(|| ready(child.try_wait().unwrap())).await;
+    assert!(status.success());
+}
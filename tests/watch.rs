@@ -0,0 +1,244 @@
+mod common;
+
+use {
+    common::{copy_dir, testdir, wait_for, wait_until},
+    std::{
+        fs,
+        future::ready,
+        net::TcpListener,
+        process::{Command, Stdio},
+        slice,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    },
+    symo::Options,
+};
+
+#[test]
+fn watcher_stops_on_drop() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let template = tempdir.path().join("template");
+    fs::create_dir_all(&model).unwrap();
+    fs::create_dir_all(&template).unwrap();
+    let count = Arc::new(AtomicUsize::new(0));
+    let watcher = symo::watch(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        None,
+        &Options::default(),
+        {
+            let count = Arc::clone(&count);
+            move |_| {
+                count.fetch_add(1, Ordering::SeqCst);
+                Ok(vec![])
+            }
+        },
+    )
+    .unwrap();
+    wait_until(|| count.load(Ordering::SeqCst) > 0);
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+
+    drop(watcher);
+    fs::write(template.join("page.md"), "page\n").unwrap();
+    thread::sleep(Duration::from_secs(2));
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn watcher_setup_error() {
+    let tempdir = tempfile::tempdir().unwrap();
+    assert!(symo::watch(
+        &[tempdir.path().join("missing")],
+        &[tempdir.path().to_path_buf()],
+        None,
+        &Options::default(),
+        |_| Ok(vec![])
+    )
+    .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn incremental_rebuild() {
+    let testdir = testdir();
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let template = tempdir.path().join("template");
+    let output = tempdir.path().join("output");
+    copy_dir(&testdir.join("data-model"), &model);
+    copy_dir(&testdir.join("data-template"), &template);
+    fs::create_dir_all(&output).unwrap();
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    tokio::spawn({
+        let (model, template, output) = (model.clone(), template.clone(), output.clone());
+        async move {
+            symo::run_serve(
+                &[model],
+                &[template],
+                Some(&output),
+                &addr,
+                &Options::default(),
+            )
+            .await
+            .unwrap();
+        }
+    });
+    wait_for(|| ready(output.join(".symo-manifest").exists().then_some(()))).await;
+    let mtime = |name: &str| fs::metadata(output.join(name)).unwrap().modified().unwrap();
+    let sample1 = mtime("sample1.md");
+    let sample3 = mtime("sample3-renamed.md");
+
+    fs::write(template.join("sample2.md"), "changed\n").unwrap();
+    wait_for(|| {
+        ready(
+            fs::read_to_string(output.join("sample2.md"))
+                .unwrap()
+                .ends_with("changed\n")
+                .then_some(()),
+        )
+    })
+    .await;
+    assert_eq!(mtime("sample1.md"), sample1);
+    assert_eq!(mtime("sample3-renamed.md"), sample3);
+}
+
+#[cfg(unix)]
+#[test]
+fn watch_mode() {
+    let testdir = testdir();
+    let tempdir = tempfile::tempdir().unwrap();
+    let template = tempdir.path().join("template");
+    let output = tempdir.path().join("output");
+    let log = tempdir.path().join("log");
+    copy_dir(&testdir.join("data-template"), &template);
+    fs::create_dir_all(&output).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_symo"))
+        .arg(testdir.join("data-model"))
+        .arg(&template)
+        .arg(&output)
+        .arg("--watch")
+        .arg("--exec")
+        .arg(format!("echo built >> '{}'", log.display()))
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let builds = || {
+        fs::read_to_string(&log)
+            .map(|log| log.lines().count())
+            .unwrap_or(0)
+    };
+    wait_until(|| builds() == 1);
+    assert!(output.join("sample1.md").exists());
+
+    thread::sleep(Duration::from_millis(500));
+    fs::write(template.join("sample2.md"), "changed\n").unwrap();
+    wait_until(|| builds() == 2);
+    assert!(fs::read_to_string(output.join("sample2.md"))
+        .unwrap()
+        .ends_with("changed\n"));
+
+    assert!(Command::new("kill")
+        .arg("-TERM")
+        .arg(child.id().to_string())
+        .status()
+        .unwrap()
+        .success());
+    wait_until(|| child.try_wait().unwrap().is_some());
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn polling_watcher() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let template = tempdir.path().join("template");
+    fs::create_dir_all(&model).unwrap();
+    fs::create_dir_all(&template).unwrap();
+    let changes = Arc::new(Mutex::new(vec![]));
+    let options = Options {
+        debounce: Some(Duration::from_millis(100)),
+        poll: Some(Duration::from_millis(100)),
+        ..Options::default()
+    };
+    let _watcher = symo::watch(
+        slice::from_ref(&model),
+        slice::from_ref(&template),
+        None,
+        &options,
+        {
+            let changes = Arc::clone(&changes);
+            move |changed| {
+                changes
+                    .lock()
+                    .unwrap()
+                    .push(changed.map(|changed| changed.to_vec()));
+                Ok(vec![])
+            }
+        },
+    )
+    .unwrap();
+    wait_until(|| changes.lock().unwrap().len() == 1);
+    assert_eq!(changes.lock().unwrap()[0], None);
+
+    thread::sleep(Duration::from_millis(500));
+    fs::write(template.join("page.md"), "page\n").unwrap();
+    wait_until(|| changes.lock().unwrap().len() > 1);
+    assert!(changes.lock().unwrap()[1]
+        .as_ref()
+        .unwrap()
+        .iter()
+        .any(|path| path.ends_with("page.md")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn outside_import() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let model = tempdir.path().join("model");
+    let shared = tempdir.path().join("shared");
+    let template = tempdir.path().join("template");
+    let output = tempdir.path().join("output");
+    [&model, &shared, &template, &output]
+        .iter()
+        .for_each(|dir| fs::create_dir_all(dir).unwrap());
+    fs::write(
+        model.join("nodes.yaml"),
+        "imports: [../shared/people.yaml]\ndiagrams:\n  people: |\n    flowchart LR\n    {{definitions}}\n    customer\n",
+    )
+    .unwrap();
+    let people = |name: &str| {
+        fs::write(
+            shared.join("people.yaml"),
+            format!("nodes:\n  customer:\n    name: {name}\n    definition: '{{{{id}}}}[{{{{name}}}}]'\n"),
+        )
+        .unwrap()
+    };
+    people("Customer");
+    fs::write(template.join("page.md"), "{{{people}}}\n").unwrap();
+
+    let options = Options {
+        debounce: Some(Duration::from_millis(100)),
+        ..Options::default()
+    };
+    tokio::spawn({
+        let (model, template, output) = (model.clone(), template.clone(), output.clone());
+        async move {
+            symo::run_watch(&[model], &[template], &output, &options, None)
+                .await
+                .unwrap();
+        }
+    });
+    let page = || fs::read_to_string(output.join("page.md")).unwrap_or_default();
+    wait_for(|| ready(page().contains("customer[Customer]").then_some(()))).await;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    people("Client");
+    wait_for(|| ready(page().contains("customer[Client]").then_some(()))).await;
+}